@@ -50,7 +50,8 @@ fn main() {
         .num_threads(3)
         .build()
         .expect("building pool failed");
-    pool.compare()
+    let comparator = pool
+        .compare()
         .runs_number(3)
         .attach_algorithm_nodisplay_with_setup(
             "seq",
@@ -87,8 +88,15 @@ fn main() {
                 // assert!(v.iter().copied().eq(1..=SIZE as u64));
                 v
             },
-        )
+        );
+    comparator
+        .generate_json("compare.json")
+        .expect("failed saving json");
+    comparator
+        .generate_csv("compare.csv")
+        .expect("failed saving csv");
+    comparator
         .generate_logs("compare.html")
         .expect("failed saving logs");
-    println!("generated compare.html");
+    println!("generated compare.html, compare.json and compare.csv");
 }
@@ -0,0 +1,238 @@
+//! Performance regression checks: comparisons against durations saved from previous runs.
+use crate::compare::time_string;
+use crate::metadata::Metadata;
+use crate::raw_events::TimeStamp;
+use crate::stats::{mann_whitney_u, median, DurationsSummary};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+/// Durations of all runs of all algorithms of a comparison, as stored in baseline files.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Baseline {
+    pub(crate) threads_number: usize,
+    /// context of the first run
+    #[serde(default)]
+    pub(crate) metadata: Metadata,
+    /// durations of all runs (sorted), for each label
+    pub(crate) durations: BTreeMap<String, Vec<TimeStamp>>,
+}
+
+impl Baseline {
+    /// Compare given durations statistics and durations of each label with ours.
+    /// Fails if we ran on a different number of threads: durations are then incomparable.
+    pub(crate) fn verdict<'l, I>(
+        &self,
+        labels_durations: I,
+        threads_number: usize,
+        threshold: f64,
+    ) -> io::Result<BaselineVerdict>
+    where
+        I: Iterator<Item = (&'l str, DurationsSummary, Vec<TimeStamp>)>,
+    {
+        if threads_number != self.threads_number {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "baseline was recorded on {} threads, we now run on {}",
+                    self.threads_number, threads_number
+                ),
+            ));
+        }
+        let labels = labels_durations
+            .map(|(label, summary, durations)| {
+                let baseline_durations = self.durations.get(label);
+                let baseline_median = baseline_durations.map(|d| {
+                    median(
+                        &d.iter()
+                            .map(|&duration| duration as f64)
+                            .collect::<Vec<_>>(),
+                    )
+                });
+                let ratio = baseline_median.map(|b| summary.median / b.max(1.0));
+                let status = match ratio {
+                    None => RegressionStatus::New,
+                    Some(r) if r > 1.0 + threshold => RegressionStatus::Regressed,
+                    Some(r) if r < 1.0 - threshold => RegressionStatus::Improved,
+                    Some(_) => RegressionStatus::Unchanged,
+                };
+                LabelVerdict {
+                    label: label.to_string(),
+                    status,
+                    median: summary.median as TimeStamp,
+                    baseline_median: baseline_median.map(|b| b as TimeStamp),
+                    ratio,
+                    p_value: baseline_durations.map(|b| mann_whitney_u(&durations, b)),
+                }
+            })
+            .collect();
+        Ok(BaselineVerdict {
+            threshold,
+            threads_number,
+            labels,
+        })
+    }
+}
+
+/// How an algorithm performs compared to its baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegressionStatus {
+    /// median duration increased beyond the threshold
+    Regressed,
+    /// median duration decreased beyond the threshold
+    Improved,
+    /// median duration is within the threshold
+    Unchanged,
+    /// algorithm is not in the baseline
+    New,
+}
+
+/// Comparison of an algorithm against its baseline.
+#[derive(Debug, Clone)]
+pub struct LabelVerdict {
+    /// label of the algorithm
+    pub label: String,
+    /// how it compares to the baseline
+    pub status: RegressionStatus,
+    /// current median duration
+    pub median: TimeStamp,
+    /// median duration in the baseline (if any)
+    pub baseline_median: Option<TimeStamp>,
+    /// current median over baseline median
+    pub ratio: Option<f64>,
+    /// p-value of a Mann-Whitney U test between current and baseline durations
+    pub p_value: Option<f64>,
+}
+
+/// Result of `Comparator::against_baseline`, for each attached algorithm.
+///
+/// It displays as a small report.
+#[derive(Debug, Clone)]
+pub struct BaselineVerdict {
+    /// relative increase of the median tolerated before reporting a regression
+    pub threshold: f64,
+    /// number of threads used (both now and for the baseline)
+    pub threads_number: usize,
+    /// verdicts for all attached algorithms, in attachment order
+    pub labels: Vec<LabelVerdict>,
+}
+
+impl BaselineVerdict {
+    /// Iterate on all regressed algorithms.
+    pub fn regressions(&self) -> impl Iterator<Item = &LabelVerdict> {
+        self.labels
+            .iter()
+            .filter(|l| l.status == RegressionStatus::Regressed)
+    }
+
+    /// Return true if any algorithm regressed.
+    pub fn has_regressions(&self) -> bool {
+        self.regressions().next().is_some()
+    }
+
+    /// Return the verdict for given label.
+    pub fn get(&self, label: &str) -> Option<&LabelVerdict> {
+        self.labels.iter().find(|l| l.label == label)
+    }
+}
+
+impl fmt::Display for BaselineVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for verdict in &self.labels {
+            match (verdict.baseline_median, verdict.ratio) {
+                (Some(baseline_median), Some(ratio)) => writeln!(
+                    f,
+                    "{}: {:?}, median {} (baseline {}, {:+.1}%, p-value {:.4})",
+                    verdict.label,
+                    verdict.status,
+                    time_string(verdict.median),
+                    time_string(baseline_median),
+                    (ratio - 1.0) * 100.0,
+                    verdict.p_value.unwrap_or(1.0)
+                )?,
+                _ => writeln!(
+                    f,
+                    "{}: {:?}, median {}",
+                    verdict.label,
+                    verdict.status,
+                    time_string(verdict.median)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::RunLog;
+    use crate::stats::Stats;
+
+    #[test]
+    fn verdicts() {
+        let baseline = Baseline {
+            threads_number: 2,
+            metadata: Metadata::unknown(),
+            durations: vec![
+                ("same".to_string(), vec![100, 101, 102, 103, 104]),
+                ("slower".to_string(), vec![100, 101, 102, 103, 104]),
+                ("faster".to_string(), vec![100, 101, 102, 103, 104]),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let labels = ["same", "slower", "faster", "new"];
+        let durations: Vec<Vec<TimeStamp>> = vec![
+            vec![99, 101, 103, 104, 105],
+            vec![120, 121, 122, 123, 124],
+            vec![80, 81, 82, 83, 84],
+            vec![10, 20],
+        ];
+        let logs: Vec<Vec<RunLog>> = durations
+            .iter()
+            .map(|runs| {
+                runs.iter()
+                    .map(|&duration| RunLog::from_tasks(2, &[(0, duration, 0, &[])]))
+                    .collect()
+            })
+            .collect();
+        let stats = Stats::get_statistics(&logs, 2, 5);
+        let current = || {
+            labels
+                .iter()
+                .cloned()
+                .zip(stats.durations_summaries())
+                .zip(durations.iter().cloned())
+                .map(|((label, summary), durations)| (label, summary, durations))
+        };
+        let verdict = baseline.verdict(current(), 2, 0.05).unwrap();
+        let statuses: Vec<_> = verdict
+            .labels
+            .iter()
+            .map(|l| (l.label.as_str(), l.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("same", RegressionStatus::Unchanged),
+                ("slower", RegressionStatus::Regressed),
+                ("faster", RegressionStatus::Improved),
+                ("new", RegressionStatus::New),
+            ]
+        );
+        assert!(verdict.has_regressions());
+        assert_eq!(verdict.regressions().count(), 1);
+        let slower = verdict.get("slower").unwrap();
+        assert_eq!((slower.median, slower.baseline_median), (122, Some(102)));
+        assert!(slower.p_value.unwrap() < 0.05);
+        let new = verdict.get("new").unwrap();
+        assert_eq!((new.median, new.ratio, new.p_value), (15, None, None));
+        let report = verdict.to_string();
+        assert!(report.contains("slower: Regressed, median 122ns (baseline 102ns, +19.6%"));
+        // medians on different numbers of threads cannot be compared
+        let error = baseline.verdict(current(), 4, 0.05).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
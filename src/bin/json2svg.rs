@@ -1,15 +1,83 @@
 extern crate rayon_logs;
 
-use rayon_logs::RunLog;
+use rayon_logs::{Aggregation, RunLog, SvgOptions};
 use std::env::args;
+use std::process::exit;
 
 fn main() {
-    let json_file = args()
-        .nth(1)
-        .expect("missing json file name as first argument");
-    let svg_file = args()
-        .nth(2)
-        .expect("missing svg file name as second argument");
-    let logs = RunLog::load(&json_file).expect("failed to load json file");
-    logs.save_svg(&svg_file).expect("failed to save svg file");
+    let (flags, files): (Vec<String>, Vec<String>) =
+        args().skip(1).partition(|arg| arg.starts_with("--"));
+    // static files get a legend unless asked otherwise
+    let mut options = if flags.iter().any(|flag| flag == "--static") {
+        SvgOptions::static_svg()
+    } else {
+        SvgOptions::default()
+    };
+    for flag in &flags {
+        let (name, value) = match flag.find('=') {
+            Some(index) => (&flag[..index], Some(&flag[index + 1..])),
+            None => (flag.as_str(), None),
+        };
+        match (name, value) {
+            ("--static", None) | ("--gantt", None) => (),
+            ("--legend", None) => options.legend = true,
+            ("--no-legend", None) => options.legend = false,
+            ("--min-pixels", Some(pixels)) => {
+                options.aggregation = Some(Aggregation::Pixels(parse(flag, pixels)))
+            }
+            ("--min-duration", Some(duration)) => {
+                options.aggregation = Some(Aggregation::Duration(parse(flag, duration)))
+            }
+            _ => {
+                eprintln!(
+                    "unknown option {} (use --static, --gantt, --legend, --no-legend, \
+                     --min-pixels=<pixels> or --min-duration=<ns>)",
+                    flag
+                );
+                exit(1)
+            }
+        }
+    }
+    let gantt = flags.iter().any(|flag| flag == "--gantt");
+    if gantt && options.aggregation.is_some() {
+        eprintln!(
+            "gantt charts cannot be aggregated \
+             (--min-pixels and --min-duration only apply to graphs)"
+        );
+        exit(1)
+    }
+    let log_file = files
+        .first()
+        .expect("missing log file name (json or binary) as first argument");
+    let svg_file = files
+        .get(1)
+        .expect("missing svg (or html) file name as second argument");
+    let logs = RunLog::load(log_file).unwrap_or_else(|e| {
+        eprintln!("failed to load {}: {}", log_file, e);
+        exit(1)
+    });
+    let violations = logs.validate();
+    if !violations.is_empty() {
+        eprintln!("{} is not a valid log:", log_file);
+        for violation in violations {
+            eprintln!("  {}", violation);
+        }
+        exit(1)
+    }
+    if svg_file.ends_with(".html") {
+        logs.save_html(svg_file)
+    } else if gantt {
+        logs.save_gantt_svg(svg_file, &options)
+    } else {
+        logs.save_svg_with_options(svg_file, &options)
+    }
+    .expect("failed to save svg file");
+}
+
+/// Parse given flag's value or exit.
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("invalid value in {}", flag);
+        exit(1)
+    })
 }
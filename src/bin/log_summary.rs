@@ -0,0 +1,30 @@
+extern crate rayon_logs;
+
+use rayon_logs::RunLog;
+use std::env::{args, var};
+use std::process::exit;
+
+fn main() {
+    let (flags, arguments): (Vec<String>, Vec<String>) =
+        args().skip(1).partition(|arg| arg.starts_with("--"));
+    let ascii = flags.iter().any(|flag| flag == "--ascii");
+    if let Some(flag) = flags.iter().find(|flag| *flag != "--ascii") {
+        eprintln!("unknown option {} (use --ascii)", flag);
+        exit(1)
+    }
+    let log_file = arguments
+        .first()
+        .expect("missing log file name (json or binary) as first argument");
+    // default to the terminal's width when the shell exports it
+    let width = arguments
+        .get(1)
+        .cloned()
+        .or_else(|| var("COLUMNS").ok())
+        .and_then(|w| w.parse().ok())
+        .unwrap_or(80);
+    let log = RunLog::load(log_file).unwrap_or_else(|e| {
+        eprintln!("failed to load {}: {}", log_file, e);
+        exit(1)
+    });
+    print!("{}", log.summary(width, ascii));
+}
@@ -0,0 +1,424 @@
+//! Compact binary format for `RunLog`s.
+//!
+//! Files start with a magic header followed by a format version.
+//! Integers are stored as LEB128 varints and timestamps are delta-encoded
+//! (zigzag encoding when deltas can be negative).
+//! This is much smaller and faster than json on logs with millions of tasks.
+use crate::log::{IdleKind, IdlePeriod, RunLog, StealLog, TaskLog};
+use crate::metadata::Metadata;
+use crate::raw_events::TimeStamp;
+use std::io::{self, ErrorKind, Read, Write};
+
+/// All binary log files start with these bytes.
+pub(crate) const MAGIC: &[u8; 4] = b"RLOG";
+/// Current version of the binary format.
+const VERSION: u8 = 1;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("varint is too long"))
+}
+
+fn write_usize<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    write_varint(writer, value as u64)
+}
+
+fn read_usize<R: Read>(reader: &mut R) -> io::Result<usize> {
+    read_varint(reader).map(|v| v as usize)
+}
+
+/// Write the (possibly negative) difference between two values.
+fn write_delta<W: Write>(writer: &mut W, value: u64, previous: u64) -> io::Result<()> {
+    let delta = value.wrapping_sub(previous) as i64;
+    write_varint(writer, ((delta << 1) ^ (delta >> 63)) as u64)
+}
+
+fn read_delta<R: Read>(reader: &mut R, previous: u64) -> io::Result<u64> {
+    let zigzag = read_varint(reader)?;
+    let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+    Ok(previous.wrapping_add(delta as u64))
+}
+
+/// Read a duration and return the end of the period starting at given time.
+fn read_end<R: Read>(reader: &mut R, start: u64) -> io::Result<u64> {
+    start
+        .checked_add(read_varint(reader)?)
+        .ok_or_else(|| invalid_data("period ends after the end of times"))
+}
+
+/// Optional values are shifted by one, 0 encoding `None`.
+fn write_option<W: Write>(writer: &mut W, value: Option<usize>) -> io::Result<()> {
+    write_usize(writer, value.map(|v| v + 1).unwrap_or(0))
+}
+
+fn read_option<R: Read>(reader: &mut R) -> io::Result<Option<usize>> {
+    read_usize(reader).map(|v| v.checked_sub(1))
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    write_usize(writer, string.len())?;
+    writer.write_all(string.as_bytes())
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let length = read_usize(reader)?;
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(length as u64)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("invalid utf8 in string"))
+}
+
+fn write_metadata<W: Write>(writer: &mut W, metadata: &Metadata) -> io::Result<()> {
+    write_varint(writer, u64::from(metadata.schema_version))?;
+    write_string(writer, &metadata.crate_version)?;
+    write_string(writer, &metadata.hostname)?;
+    write_usize(writer, metadata.cpus_number)?;
+    write_varint(writer, metadata.start_time)?;
+    write_usize(writer, metadata.command_line.len())?;
+    for argument in &metadata.command_line {
+        write_string(writer, argument)?;
+    }
+    write_string(writer, &metadata.clock)?;
+    write_usize(writer, metadata.user.len())?;
+    for (key, value) in &metadata.user {
+        write_string(writer, key)?;
+        write_string(writer, value)?;
+    }
+    Ok(())
+}
+
+fn read_metadata<R: Read>(reader: &mut R) -> io::Result<Metadata> {
+    Ok(Metadata {
+        schema_version: read_varint(reader)? as u32,
+        crate_version: read_string(reader)?,
+        hostname: read_string(reader)?,
+        cpus_number: read_usize(reader)?,
+        start_time: read_varint(reader)?,
+        command_line: (0..read_usize(reader)?)
+            .map(|_| read_string(reader))
+            .collect::<io::Result<_>>()?,
+        clock: read_string(reader)?,
+        user: (0..read_usize(reader)?)
+            .map(|_| Ok((read_string(reader)?, read_string(reader)?)))
+            .collect::<io::Result<_>>()?,
+    })
+}
+
+/// Write given log (header included) in binary format.
+pub(crate) fn write_log<W: Write>(writer: &mut W, log: &RunLog) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    write_usize(writer, log.threads_number)?;
+    write_varint(writer, log.duration)?;
+    writer.write_all(&[log.partial as u8])?;
+    write_metadata(writer, &log.metadata)?;
+
+    write_usize(writer, log.tags.len())?;
+    for tag in &log.tags {
+        write_string(writer, tag)?;
+    }
+
+    write_usize(writer, log.tasks_logs.len())?;
+    let mut previous_start = 0;
+    for (task_id, task) in log.tasks_logs.iter().enumerate() {
+        write_delta(writer, task.start_time, previous_start)?;
+        previous_start = task.start_time;
+        let duration = task
+            .end_time
+            .checked_sub(task.start_time)
+            .ok_or_else(|| invalid_data("task ends before starting"))?;
+        write_varint(writer, duration)?;
+        write_usize(writer, task.thread_id)?;
+        write_usize(writer, task.children.len())?;
+        for child in &task.children {
+            // children ids are close to ours
+            write_delta(writer, *child as u64, task_id as u64)?;
+        }
+    }
+
+    write_usize(writer, log.subgraphs.len())?;
+    for &(start_task, end_task, tag_id, work_amount) in &log.subgraphs {
+        write_usize(writer, start_task)?;
+        write_usize(writer, end_task)?;
+        write_usize(writer, tag_id)?;
+        write_usize(writer, work_amount)?;
+    }
+    write_usize(writer, log.subgraphs_parents.len())?;
+    for &parent in &log.subgraphs_parents {
+        write_option(writer, parent)?;
+    }
+
+    write_usize(writer, log.steals.len())?;
+    let mut previous_time = 0;
+    for steal in &log.steals {
+        write_delta(writer, steal.time, previous_time)?;
+        previous_time = steal.time;
+        write_usize(writer, steal.thief)?;
+        write_option(writer, steal.victim)?;
+        write_option(writer, steal.task)?;
+        write_usize(writer, steal.attempts)?;
+    }
+
+    write_usize(writer, log.idle_periods.len())?;
+    let mut previous_start = 0;
+    for period in &log.idle_periods {
+        write_delta(writer, period.start, previous_start)?;
+        previous_start = period.start;
+        let duration = period
+            .end
+            .checked_sub(period.start)
+            .ok_or_else(|| invalid_data("idle period ends before starting"))?;
+        write_varint(writer, duration)?;
+        write_usize(writer, period.thread_id)?;
+        let kind = match period.kind {
+            IdleKind::Searching => 0,
+            IdleKind::Sleeping => 1,
+        };
+        writer.write_all(&[kind])?;
+    }
+    Ok(())
+}
+
+/// Read a binary log. The magic header has already been consumed.
+pub(crate) fn read_log<R: Read>(reader: &mut R) -> io::Result<RunLog> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    let version = byte[0];
+    if version != VERSION {
+        return Err(invalid_data("unsupported binary log version"));
+    }
+    let threads_number = read_usize(reader)?;
+    let duration: TimeStamp = read_varint(reader)?;
+    reader.read_exact(&mut byte)?;
+    let partial = byte[0] != 0;
+    let metadata = read_metadata(reader)?;
+
+    let tags = (0..read_usize(reader)?)
+        .map(|_| read_string(reader))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let tasks_number = read_usize(reader)?;
+    let mut tasks_logs = Vec::new();
+    let mut previous_start = 0;
+    for task_id in 0..tasks_number {
+        let start_time = read_delta(reader, previous_start)?;
+        previous_start = start_time;
+        let end_time = read_end(reader, start_time)?;
+        let thread_id = read_usize(reader)?;
+        let children = (0..read_usize(reader)?)
+            .map(|_| read_delta(reader, task_id as u64).map(|c| c as usize))
+            .collect::<io::Result<Vec<_>>>()?;
+        tasks_logs.push(TaskLog {
+            start_time,
+            end_time,
+            thread_id,
+            children,
+        });
+    }
+
+    let subgraphs = (0..read_usize(reader)?)
+        .map(|_| {
+            Ok((
+                read_usize(reader)?,
+                read_usize(reader)?,
+                read_usize(reader)?,
+                read_usize(reader)?,
+            ))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let subgraphs_parents = (0..read_usize(reader)?)
+        .map(|_| read_option(reader))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut steals = Vec::new();
+    let mut previous_time = 0;
+    for _ in 0..read_usize(reader)? {
+        let time = read_delta(reader, previous_time)?;
+        previous_time = time;
+        steals.push(StealLog {
+            time,
+            thief: read_usize(reader)?,
+            victim: read_option(reader)?,
+            task: read_option(reader)?,
+            attempts: read_usize(reader)?,
+        });
+    }
+
+    let mut idle_periods = Vec::new();
+    let mut previous_start = 0;
+    for _ in 0..read_usize(reader)? {
+        let start = read_delta(reader, previous_start)?;
+        previous_start = start;
+        let end = read_end(reader, start)?;
+        let thread_id = read_usize(reader)?;
+        reader.read_exact(&mut byte)?;
+        let kind = match byte[0] {
+            0 => IdleKind::Searching,
+            1 => IdleKind::Sleeping,
+            _ => return Err(invalid_data("unknown idle period kind")),
+        };
+        idle_periods.push(IdlePeriod {
+            thread_id,
+            start,
+            end,
+            kind,
+        });
+    }
+
+    Ok(RunLog {
+        threads_number,
+        tasks_logs,
+        duration,
+        tags,
+        subgraphs,
+        subgraphs_parents,
+        steals,
+        idle_periods,
+        partial,
+        metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_log() -> RunLog {
+        let mut log = RunLog::from_tasks(
+            2,
+            &[
+                (0, 10, 0, &[1, 2]),
+                (10, 100, 0, &[3]),
+                (20, 200, 1, &[3]),
+                (200, 210, 0, &[]),
+            ],
+        );
+        log.metadata.hostname = "héllo".to_string();
+        log.metadata
+            .user
+            .insert("input size".to_string(), "100".to_string());
+        log.tags = vec!["sum".to_string(), "leaf".to_string()];
+        log.subgraphs = vec![(0, 3, 0, 100), (1, 1, 1, 50)];
+        log.subgraphs_parents = vec![None, Some(0)];
+        log.steals = vec![
+            StealLog {
+                time: 15,
+                thief: 1,
+                victim: None,
+                task: None,
+                attempts: 3,
+            },
+            StealLog {
+                time: 20,
+                thief: 1,
+                victim: Some(0),
+                task: Some(2),
+                attempts: 1,
+            },
+        ];
+        log.idle_periods = vec![
+            IdlePeriod {
+                thread_id: 1,
+                start: 0,
+                end: 20,
+                kind: IdleKind::Searching,
+            },
+            IdlePeriod {
+                thread_id: 0,
+                start: 100,
+                end: 200,
+                kind: IdleKind::Sleeping,
+            },
+        ];
+        log.partial = true;
+        log
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for &value in &[0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+            assert_eq!(read_varint(&mut &bytes[..]).unwrap(), value);
+        }
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 300).unwrap();
+        assert_eq!(bytes, vec![0xac, 0x02]);
+        assert!(read_varint(&mut &[0xff; 10][..]).is_err());
+    }
+
+    #[test]
+    fn zigzag_deltas_round_trip() {
+        for &(value, previous) in &[(5, 3), (3, 5), (0, u64::MAX), (u64::MAX, 0), (7, 7)] {
+            let mut bytes = Vec::new();
+            write_delta(&mut bytes, value, previous).unwrap();
+            assert_eq!(read_delta(&mut &bytes[..], previous).unwrap(), value);
+        }
+        // small negative deltas stay small
+        let mut bytes = Vec::new();
+        write_delta(&mut bytes, 3, 4).unwrap();
+        assert_eq!(bytes, vec![1]);
+    }
+
+    #[test]
+    fn overflowing_ends_are_rejected() {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 2).unwrap();
+        assert_eq!(read_end(&mut &bytes[..], 3).unwrap(), 5);
+        let error = read_end(&mut &bytes[..], u64::MAX - 1).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn logs_round_trip() {
+        let log = sample_log();
+        let mut bytes = Vec::new();
+        write_log(&mut bytes, &log).unwrap();
+        assert!(bytes.starts_with(MAGIC));
+        let read_back = read_log(&mut &bytes[MAGIC.len()..]).unwrap();
+        assert_eq!(read_back, log);
+        // truncated files are errors, not panics
+        assert!(read_log(&mut &bytes[MAGIC.len()..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn invalid_logs_are_not_written() {
+        let mut log = sample_log();
+        log.tasks_logs[1].end_time = 5;
+        let error = write_log(&mut Vec::new(), &log).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let mut log = sample_log();
+        log.idle_periods[0].end = 0;
+        log.idle_periods[0].start = 1;
+        let error = write_log(&mut Vec::new(), &log).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -1,15 +1,38 @@
-use crate::pool::LOGS;
+use crate::pool::{IdleRecorder, LOGS};
+use crate::raw_events::RayonEvent;
 use crate::storage::Storage;
 use crate::ThreadPool;
 use rayon::{self, ThreadPoolBuildError};
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::ErrorKind;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 type Builder = rayon::ThreadPoolBuilder;
 
+/// Name of the file marking a directory as used by a streaming pool.
+const STREAM_LOCK: &str = "rayon_logs.lock";
+
+/// How threads store their logs.
+#[derive(Default)]
+enum StorageMode {
+    /// Everything in memory.
+    #[default]
+    Memory,
+    /// Full blocks are streamed to files inside given directory.
+    Stream(PathBuf),
+    /// Only the last (given number of) events are kept in memory.
+    FlightRecorder(usize),
+}
+
 /// We rewrite ThreadPoolBuilders since we need to overload the start handler
 /// in order to give each thread a place to write its logs.
 #[derive(Default)]
 pub struct ThreadPoolBuilder {
     builder: Builder,
+    steal_callback: Option<Box<dyn Fn(usize) -> Option<()> + Send + Sync>>,
+    storage_mode: StorageMode,
+    metadata: BTreeMap<String, String>,
 }
 
 impl ThreadPoolBuilder {
@@ -17,6 +40,9 @@ impl ThreadPoolBuilder {
     pub fn new() -> Self {
         ThreadPoolBuilder {
             builder: Builder::new(),
+            steal_callback: None,
+            storage_mode: StorageMode::Memory,
+            metadata: BTreeMap::new(),
         }
     }
 
@@ -24,33 +50,170 @@ impl ThreadPoolBuilder {
     pub fn num_threads(self, threads_number: usize) -> Self {
         ThreadPoolBuilder {
             builder: self.builder.num_threads(threads_number),
+            ..self
         }
     }
-    
+
     /// Set a steal function, see https://github.com/ma1ko/rayon/commit/78c551f105badc590a1ae6be7cddb3094663f4eb
+    ///
+    /// We rely on the following semantics of the fork: the callback is called by a worker
+    /// thread (with its index) each time it looked for work to steal and found none,
+    /// and its result is handed back to rayon.
+    /// We always install our own callback (recording failed steals, see `RunLog::steals`)
+    /// which then calls yours and returns your result (`None` if you set no callback).
     pub fn steal_callback<H>(mut self, steal_callback: H) -> Self
     where
         H: Fn(usize) -> Option<()> + Send + Sync + 'static,
     {
-        self.builder = self.builder.steal_callback(steal_callback);
+        self.steal_callback = Some(Box::new(steal_callback));
+        self
+    }
+
+    /// Stream logs to disk during execution instead of keeping them all in memory.
+    /// Each thread appends its events to a "thread_<index>.bin" file inside given directory.
+    /// Only one pool at a time can stream to a directory: we mark it with a "rayon_logs.lock"
+    /// file, removed when the pool is dropped.
+    /// If the directory is already in use (or the lock cannot be created)
+    /// logging installs fail (see `ThreadPool::try_logging_install`) and nothing gets
+    /// written to disk.
+    /// You might need to remove the lock by hand after a crash.
+    /// This is useful for very long runs since memory usage stays bounded.
+    /// Logs are read back from the files at the end of each `logging_install` and
+    /// can also be re-loaded later with `RunLog::load_streamed`.
+    pub fn stream_logs<P: Into<PathBuf>>(self, directory: P) -> Self {
+        ThreadPoolBuilder {
+            storage_mode: StorageMode::Stream(directory.into()),
+            ..self
+        }
+    }
+
+    /// Only keep the last `events_number` (rounded up) events of each thread in memory,
+    /// overwriting older ones.
+    /// Logs are then kept accross calls to `install` and you can get a log of the recent
+    /// history at any time with `ThreadPool::flight_recorder_log`
+    /// (for example when some deadline is missed).
+    /// Obtained logs might be partial (see `RunLog::partial`).
+    pub fn flight_recorder(self, events_number: usize) -> Self {
+        ThreadPoolBuilder {
+            storage_mode: StorageMode::FlightRecorder(events_number),
+            ..self
+        }
+    }
+
+    /// Attach given key/value pair to the metadata of all logs produced by the pool
+    /// (see `RunLog::metadata`).
+    /// This is useful to record the parameters of an experiment (input size, algorithm...).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..10_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .metadata("input size", v.len().to_string())
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// assert_eq!(log.metadata.user["input size"], "10000");
+    /// assert_eq!(log.metadata.schema_version, rayon_logs::SCHEMA_VERSION);
+    /// ```
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.metadata.insert(key.into(), value.into());
         self
     }
 
     /// Build the `ThreadPool`.
+    ///
+    /// Besides the steal callback, we rely on two more hooks of the fork to record what idle
+    /// threads do (see `RunLog::idle_periods`).
+    /// Both are called by a worker thread with its index and a boolean:
+    /// - `steal_loop_callback` with `true` when the thread runs out of work and starts
+    ///   looking for some to steal and `false` when it stops (it found some or the job it was
+    ///   waiting for completed);
+    /// - `sleep_callback` with `true` right before the thread blocks on rayon's sleep condition
+    ///   variable and `false` right after it wakes up (it is then still looking for work).
     pub fn build(self) -> Result<ThreadPool, ThreadPoolBuildError> {
         let logs = Arc::new(Mutex::new(Vec::new()));
         let shared_logs = logs.clone();
+        let user_steal_callback = self.steal_callback;
+        let storage_mode = self.storage_mode;
+        let metadata = self.metadata;
+        let flight_recorder = matches!(storage_mode, StorageMode::FlightRecorder(_));
+        let stream_lock = if let StorageMode::Stream(ref directory) = storage_mode {
+            // if this fails, the error will come back when creating the lock
+            create_dir_all(directory).ok();
+            let lock = directory.join(STREAM_LOCK);
+            match OpenOptions::new().write(true).create_new(true).open(&lock) {
+                Ok(_) => Ok(Some(lock)),
+                Err(e) => {
+                    let reason = if e.kind() == ErrorKind::AlreadyExists {
+                        format!("in use by another pool (or remove {})", lock.display())
+                    } else {
+                        e.to_string()
+                    };
+                    let message =
+                        format!("cannot stream logs to {}: {}", directory.display(), reason);
+                    Err((e.kind(), message))
+                }
+            }
+        } else {
+            Ok(None)
+        };
+        let streaming = matches!(stream_lock, Ok(Some(_)));
+        let idle_recorder = Arc::new(IdleRecorder::default());
+        let steal_recorder = idle_recorder.clone();
+        let search_recorder = idle_recorder.clone();
+        let sleep_recorder = idle_recorder.clone();
         let pool = self
             .builder
-            .start_handler(move |_| {
+            .steal_callback(move |thread_index| {
+                steal_recorder.log_failed_steal();
+                user_steal_callback
+                    .as_ref()
+                    .and_then(|callback| callback(thread_index))
+            })
+            .steal_loop_callback(move |_, searching| {
+                search_recorder.log_transition(if searching {
+                    RayonEvent::SearchStart
+                } else {
+                    RayonEvent::SearchEnd
+                })
+            })
+            .sleep_callback(move |_, sleeping| {
+                sleep_recorder.log_transition(if sleeping {
+                    RayonEvent::SleepStart
+                } else {
+                    RayonEvent::SleepEnd
+                })
+            })
+            .start_handler(move |thread_index| {
                 LOGS.with(|l| {
-                    let thread_storage = Arc::new(Storage::new());
+                    let thread_storage = Arc::new(match storage_mode {
+                        StorageMode::Stream(ref directory) if streaming => Storage::streaming_to(
+                            directory.join(format!("thread_{}.bin", thread_index)),
+                        ),
+                        // directory in use by someone else: installs will fail anyway
+                        StorageMode::Memory | StorageMode::Stream(_) => Storage::new(),
+                        StorageMode::FlightRecorder(events_number) => {
+                            Storage::bounded(events_number)
+                        }
+                    });
                     shared_logs.lock().unwrap().push(thread_storage.clone());
                     *l.borrow_mut() = thread_storage;
                 });
             })
             .build();
 
-        pool.map(|p| ThreadPool { pool: p, logs })
+        pool.map(|p| ThreadPool {
+            pool: p,
+            logs,
+            flight_recorder,
+            metadata,
+            stream_lock,
+            idle_recorder,
+        })
     }
 }
@@ -0,0 +1,189 @@
+//! Export logs in the Trace Event Format used by chrome://tracing and Perfetto.
+//! See https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+use crate::log::RunLog;
+use crate::raw_events::{TaskId, TimeStamp};
+use itertools::Itertools;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::iter::once;
+
+/// Trace event format timestamps are in micro seconds.
+fn micro_seconds(time: TimeStamp) -> f64 {
+    time as f64 / 1000.0
+}
+
+/// Write all given events, separated by commas.
+fn write_events<W: Write, I: Iterator<Item = Value>>(
+    writer: &mut W,
+    events: I,
+    first: &mut bool,
+) -> io::Result<()> {
+    for event in events {
+        if !*first {
+            writer.write_all(b",\n")?;
+        }
+        *first = false;
+        serde_json::to_writer(&mut *writer, &event)?;
+    }
+    Ok(())
+}
+
+/// Write given log as a chrome trace.
+/// Tasks are complete events on their thread's track, categorized by the tags of all
+/// subgraphs containing them.
+/// Links between tasks on different threads are flow events.
+pub(crate) fn write_chrome_trace<W: Write>(log: &RunLog, writer: &mut W) -> io::Result<()> {
+    // tags (and work amounts) for each task
+    let mut tasks_tags: HashMap<TaskId, Vec<(usize, usize)>> = HashMap::new();
+    for (start_task, end_task, tag_id, work_amount) in &log.subgraphs {
+        for task in log.tasks_between(*start_task, *end_task) {
+            tasks_tags
+                .entry(task)
+                .or_default()
+                .push((*tag_id, *work_amount));
+        }
+    }
+
+    writer.write_all(b"{\"traceEvents\":[\n")?;
+    let mut first = true;
+
+    let names = once(name_event("process_name", None, "rayon_logs")).chain(
+        (0..log.threads_number)
+            .map(|thread| name_event("thread_name", Some(thread), &format!("thread {}", thread))),
+    );
+    write_events(writer, names, &mut first)?;
+
+    let tasks = log.tasks_logs.iter().enumerate().map(|(task_id, task)| {
+        let tags = tasks_tags.get(&task_id);
+        let category = tags
+            .map(|tags| tags.iter().map(|&(tag, _)| &log.tags[tag]).join(","))
+            .unwrap_or_else(|| "task".to_string());
+        let mut args = Map::new();
+        args.insert("task".to_string(), json!(task_id));
+        for &(tag, work_amount) in tags.into_iter().flatten() {
+            args.insert(log.tags[tag].clone(), json!(work_amount));
+        }
+        json!({
+            "name": format!("task {}", task_id),
+            "cat": category,
+            "ph": "X",
+            "ts": micro_seconds(task.start_time),
+            "dur": micro_seconds(task.duration()),
+            "pid": 0,
+            "tid": task.thread_id,
+            "args": args,
+        })
+    });
+    write_events(writer, tasks, &mut first)?;
+
+    let idle_periods = log.idle_periods.iter().map(|period| {
+        json!({
+            "name": period.kind.label(),
+            "cat": "idle",
+            "ph": "X",
+            "ts": micro_seconds(period.start),
+            "dur": micro_seconds(period.end - period.start),
+            "pid": 0,
+            "tid": period.thread_id,
+        })
+    });
+    write_events(writer, idle_periods, &mut first)?;
+
+    let flows = log
+        .tasks_logs
+        .iter()
+        .enumerate()
+        .flat_map(|(task_id, task)| task.children.iter().map(move |&c| (task_id, task, c)))
+        .filter(|&(_, task, child)| log.tasks_logs[child].thread_id != task.thread_id)
+        .enumerate()
+        .flat_map(|(flow_id, (task_id, task, child))| {
+            let child_task = &log.tasks_logs[child];
+            // flows get bound to the slices enclosing their timestamps:
+            // children are created when their father ends
+            vec![
+                json!({
+                    "name": "dependency",
+                    "cat": "dependency",
+                    "ph": "s",
+                    "id": flow_id,
+                    "ts": micro_seconds(task.end_time),
+                    "pid": 0,
+                    "tid": task.thread_id,
+                    "args": {"from": task_id, "to": child},
+                }),
+                json!({
+                    "name": "dependency",
+                    "cat": "dependency",
+                    "ph": "f",
+                    "bp": "e",
+                    "id": flow_id,
+                    "ts": micro_seconds(child_task.start_time),
+                    "pid": 0,
+                    "tid": child_task.thread_id,
+                }),
+            ]
+        });
+    write_events(writer, flows, &mut first)?;
+
+    writer.write_all(b"\n],\"displayTimeUnit\":\"ns\",\"otherData\":")?;
+    serde_json::to_writer(&mut *writer, &log.metadata)?;
+    writer.write_all(b"}\n")
+}
+
+/// Return a metadata event naming the process or a thread.
+fn name_event(kind: &str, thread: Option<usize>, name: &str) -> Value {
+    let mut event = json!({
+        "name": kind,
+        "ph": "M",
+        "pid": 0,
+        "args": {"name": name},
+    });
+    if let Some(thread) = thread {
+        event["tid"] = json!(thread);
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flows_go_from_fathers_ends_to_children_starts() {
+        let log = RunLog::from_tasks(
+            2,
+            &[
+                (0, 10_000, 0, &[1, 2]),
+                (10_000, 100_000, 0, &[3]),
+                (20_000, 200_000, 1, &[3]),
+                (200_000, 210_000, 0, &[]),
+            ],
+        );
+        let mut trace = Vec::new();
+        write_chrome_trace(&log, &mut trace).unwrap();
+        let trace: Value = serde_json::from_slice(&trace).unwrap();
+        let flows: Vec<(String, f64, u64)> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|e| e["cat"] == "dependency")
+            .map(|e| {
+                (
+                    e["ph"].as_str().unwrap().to_string(),
+                    e["ts"].as_f64().unwrap(),
+                    e["tid"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            flows,
+            vec![
+                ("s".to_string(), 10.0, 0),
+                ("f".to_string(), 20.0, 1),
+                ("s".to_string(), 200.0, 1),
+                ("f".to_string(), 200.0, 0),
+            ]
+        );
+    }
+}
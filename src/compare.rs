@@ -1,30 +1,57 @@
 //! `Comparator` Structure for easy comparisons of different algorithms.
-use crate::stats::Stats;
-use crate::ThreadPool;
+use crate::baseline::{Baseline, BaselineVerdict};
+use crate::profile::csv_field;
+use crate::stats::{significance_marker, Stats};
+use crate::svg::{fill_svg_file, SvgOptions};
+use crate::visualisation;
 use crate::{
-    log::RunLog,
-    svg::{histogram, HISTOGRAM_COLORS},
+    log::{IdleKind, RunLog},
+    raw_events::TimeStamp,
+    svg::{histogram, line_chart, log_log_chart, HISTOGRAM_COLORS},
 };
-use crate::{svg::fill_svg_file, visualisation};
+use crate::{ThreadPool, ThreadPoolBuilder};
 use itertools::{izip, Itertools};
-use std::collections::HashMap;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::Error;
-use std::iter::repeat_with;
+use std::io::{BufReader, Error, ErrorKind};
+use std::iter::{once, repeat_with};
 use std::path::Path;
 
 /// The comparator structure enables you to easily compare performances of different algorithms.
 ///
 /// It runs each algorithm several times before displaying some simple statistics and for each
 /// algorithm the median and best execution trace.
+/// Traces are svg animations showing each task's label on hover; for switching between tags
+/// record the algorithm with `ThreadPool::logging_install` and open it with `RunLog::save_html`.
 /// See for example the `filter_collect` example.
+///
+/// With `threads_sweep`, algorithms also run on pools of different sizes
+/// in order to display how they scale.
+/// Algorithms attached with `attach_parametrized` run on inputs of different sizes.
 pub struct Comparator<'a> {
     labels: Vec<String>,
     logs: Vec<Vec<RunLog>>,
     pool: &'a ThreadPool,
     runs_number: usize,
     display_preferences: Vec<bool>,
+    regression_threshold: f64,
+    /// additional pools for scaling studies
+    sweep_pools: Vec<ThreadPool>,
+    /// for each additional pool, the logs of all algorithms
+    sweep_logs: Vec<Vec<Vec<RunLog>>>,
+    parametrized: Vec<Parametrized>,
+    /// misuse of the api detected while building us, reported when generating logs
+    misuse: Option<&'static str>,
+}
+
+/// Runs of an algorithm for several input sizes.
+struct Parametrized {
+    label: String,
+    sizes: Vec<usize>,
+    /// sorted runs for each size
+    logs: Vec<Vec<RunLog>>,
 }
 
 impl<'a> Comparator<'a> {
@@ -35,21 +62,35 @@ impl<'a> Comparator<'a> {
             pool,
             runs_number: 100,
             display_preferences: Vec::new(),
+            regression_threshold: 0.05,
+            sweep_pools: Vec::new(),
+            sweep_logs: Vec::new(),
+            parametrized: Vec::new(),
+            misuse: None,
         }
     }
     /// Renumber all tags accross all logs such that tags number match.
     /// Return vector of all tags.
+    /// This includes the logs of sweeps and parametrized algorithms.
     fn fuse_tags(&mut self) -> Vec<String> {
         let mut global_tags = HashMap::new();
-        for experiment in &self.logs {
-            for log in experiment {
-                log.scan_tags(&mut global_tags);
-            }
+        let all_logs = self
+            .logs
+            .iter()
+            .chain(self.sweep_logs.iter().flatten())
+            .chain(self.parametrized.iter().flat_map(|p| p.logs.iter()))
+            .flatten();
+        for log in all_logs {
+            log.scan_tags(&mut global_tags);
         }
-        for experiment in &mut self.logs {
-            for log in experiment {
-                log.update_tags(&global_tags);
-            }
+        let all_logs = self
+            .logs
+            .iter_mut()
+            .chain(self.sweep_logs.iter_mut().flatten())
+            .chain(self.parametrized.iter_mut().flat_map(|p| p.logs.iter_mut()))
+            .flatten();
+        for log in all_logs {
+            log.update_tags(&global_tags);
         }
         global_tags
             .into_iter()
@@ -61,19 +102,69 @@ impl<'a> Comparator<'a> {
     /// PRECONDITION: call that BEFORE attaching algorithms
     pub fn runs_number(self, runs_wanted: usize) -> Self {
         Comparator {
-            labels: self.labels,
-            logs: self.logs,
-            pool: self.pool,
             runs_number: runs_wanted,
-            display_preferences: self.display_preferences,
+            ..self
+        }
+    }
+
+    /// Sets the relative increase of median durations tolerated by `against_baseline`
+    /// (default is 0.05 for 5%).
+    pub fn regression_threshold(self, threshold: f64) -> Self {
+        Comparator {
+            regression_threshold: threshold,
+            ..self
         }
     }
 
-    fn record_experiments<F: FnMut() -> RunLog>(&self, run_function: F) -> Vec<RunLog> {
-        let mut experiments_logs: Vec<_> =
-            repeat_with(run_function).take(self.runs_number).collect();
-        experiments_logs.sort_unstable_by_key(|run| run.duration);
-        experiments_logs
+    /// Also run all algorithms on pools with given numbers of threads.
+    /// The report then displays speedup and efficiency curves together with
+    /// idle times for each number of threads.
+    /// All other statistics are for the comparator's pool.
+    ///
+    /// It must be called before attaching (non parametrized) algorithms:
+    /// otherwise nothing runs on the additional pools and `generate_logs` fails.
+    pub fn threads_sweep(self, threads_numbers: &[usize]) -> Self {
+        if !self.logs.is_empty() {
+            return Comparator {
+                misuse: Some("threads_sweep must be called before attaching algorithms"),
+                ..self
+            };
+        }
+        let pool_threads_number = self.pool.pool.current_num_threads();
+        let sweep_pools: Vec<ThreadPool> = threads_numbers
+            .iter()
+            .filter(|&&threads_number| threads_number != pool_threads_number)
+            .sorted()
+            .dedup()
+            .map(|&threads_number| {
+                self.pool
+                    .metadata
+                    .iter()
+                    .fold(
+                        ThreadPoolBuilder::new().num_threads(threads_number),
+                        |builder, (key, value)| builder.metadata(key.as_str(), value.as_str()),
+                    )
+                    .build()
+                    .expect("building sweep pool failed")
+            })
+            .collect();
+        Comparator {
+            sweep_logs: repeat_with(Vec::new).take(sweep_pools.len()).collect(),
+            sweep_pools,
+            ..self
+        }
+    }
+
+    /// Run given function on all pools and return the logs for the comparator's pool.
+    /// Logs for the sweep pools are stored.
+    fn record_experiments<F: FnMut(&ThreadPool) -> RunLog>(
+        &mut self,
+        mut run_function: F,
+    ) -> Vec<RunLog> {
+        for (pool, logs) in self.sweep_pools.iter().zip(self.sweep_logs.iter_mut()) {
+            logs.push(record_runs(self.runs_number, || run_function(pool)));
+        }
+        record_runs(self.runs_number, || run_function(self.pool))
     }
 
     /// Log an algorithm's performances but do not generate svg traces.
@@ -82,7 +173,7 @@ impl<'a> Comparator<'a> {
         A: Fn() + Send + Sync,
         STR: Into<String>,
     {
-        let logs = self.record_experiments(|| self.pool.logging_install(&algorithm).1);
+        let logs = self.record_experiments(|pool| pool.logging_install(&algorithm).1);
         self.logs.push(logs);
         self.labels.push(label.into());
         self.display_preferences.push(false);
@@ -94,7 +185,7 @@ impl<'a> Comparator<'a> {
         A: Fn() + Send + Sync,
         STR: Into<String>,
     {
-        let logs = self.record_experiments(|| self.pool.logging_install(&algorithm).1);
+        let logs = self.record_experiments(|pool| pool.logging_install(&algorithm).1);
         self.logs.push(logs);
         self.labels.push(label.into());
         self.display_preferences.push(true);
@@ -116,9 +207,9 @@ impl<'a> Comparator<'a> {
         T: Send + Sync,
         STR: Into<String>,
     {
-        let logs = self.record_experiments(|| {
+        let logs = self.record_experiments(|pool| {
             let input = setup_function();
-            self.pool.logging_install(|| algorithm(input)).1
+            pool.logging_install(|| algorithm(input)).1
         });
         self.logs.push(logs);
         self.labels.push(label.into());
@@ -141,9 +232,9 @@ impl<'a> Comparator<'a> {
         T: Send + Sync,
         STR: Into<String>,
     {
-        let logs = self.record_experiments(|| {
+        let logs = self.record_experiments(|pool| {
             let input = setup_function();
-            self.pool.logging_install(|| algorithm(input)).1
+            pool.logging_install(|| algorithm(input)).1
         });
         self.logs.push(logs);
         self.labels.push(label.into());
@@ -151,8 +242,482 @@ impl<'a> Comparator<'a> {
         self
     }
 
+    /// Log an algorithm on inputs of each given size, prepared (un-timed) by the setup function.
+    /// The report plots durations against sizes, displays the throughput of each tag
+    /// and shows sizes where an algorithm overtakes another one.
+    /// Parametrized algorithms only run on the comparator's pool and do not appear
+    /// in the other statistics.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let sizes = [1_000, 10_000, 100_000, 1_000_000];
+    /// pool.compare()
+    ///     .runs_number(10)
+    ///     .attach_parametrized(
+    ///         "sequential",
+    ///         &sizes,
+    ///         |size| (0..size as u64).collect::<Vec<u64>>(),
+    ///         |v| v.iter().sum::<u64>(),
+    ///     )
+    ///     .attach_parametrized(
+    ///         "parallel",
+    ///         &sizes,
+    ///         |size| (0..size as u64).collect::<Vec<u64>>(),
+    ///         |v| v.par_iter().sum::<u64>(),
+    ///     )
+    ///     .generate_logs(std::env::temp_dir().join("sums.html"))
+    ///     .expect("failed saving logs");
+    /// ```
+    pub fn attach_parametrized<A, I, S, T, STR>(
+        mut self,
+        label: STR,
+        sizes: &[usize],
+        mut setup_function: S,
+        algorithm: A,
+    ) -> Self
+    where
+        S: FnMut(usize) -> I,
+        I: Send,
+        A: Fn(I) -> T + Send + Sync,
+        T: Send + Sync,
+        STR: Into<String>,
+    {
+        let sizes: Vec<usize> = sizes.iter().cloned().sorted().dedup().collect();
+        let logs = sizes
+            .iter()
+            .map(|&size| {
+                record_runs(self.runs_number, || {
+                    let input = setup_function(size);
+                    self.pool.logging_install(|| algorithm(input)).1
+                })
+            })
+            .collect();
+        self.parametrized.push(Parametrized {
+            label: label.into(),
+            sizes,
+            logs,
+        });
+        self
+    }
+
+    /// Iterate on all runs with their algorithm's label, input size, index and tags times.
+    /// This includes the runs of all threads sweeps (look at their `threads_number`)
+    /// and the runs of parametrized algorithms (the only ones with an input size).
+    /// Runs of each algorithm are sorted by duration.
+    fn runs(
+        &self,
+    ) -> impl Iterator<Item = (&str, Option<usize>, usize, &RunLog, BTreeMap<String, u64>)> {
+        let fixed_runs = once(&self.logs)
+            .chain(self.sweep_logs.iter())
+            .flat_map(move |logs| self.labels.iter().zip(logs.iter()))
+            .map(|(label, runs)| (label, None, runs));
+        let parametrized_runs = self.parametrized.iter().flat_map(|parametrized| {
+            parametrized
+                .sizes
+                .iter()
+                .zip(parametrized.logs.iter())
+                .map(move |(&size, runs)| (&parametrized.label, Some(size), runs))
+        });
+        fixed_runs
+            .chain(parametrized_runs)
+            .flat_map(|(label, size, runs)| {
+                runs.iter().enumerate().map(move |(index, run)| {
+                    let tags_times = run
+                        .stats()
+                        .into_iter()
+                        .map(|(tag, (_, time))| (tag, time))
+                        .collect();
+                    (label.as_str(), size, index, run, tags_times)
+                })
+            })
+    }
+
+    /// Write one json record for each run of each algorithm (on each pool of the threads
+    /// sweep and for each input size of parametrized algorithms), with its duration,
+    /// idle time, number of threads, input size (null if not parametrized)
+    /// and the time spent in each tag.
+    /// Call it before `generate_logs` to get both files.
+    pub fn generate_json<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error> {
+        let records: Vec<_> = self
+            .runs()
+            .map(|(label, size, index, run, tags_times)| {
+                json!({
+                    "algorithm": label,
+                    "size": size,
+                    "run": index,
+                    "threads_number": run.threads_number,
+                    "duration": run.duration,
+                    "idle_time": idle_time(run),
+                    "tags": tags_times,
+                })
+            })
+            .collect();
+        let mut file = File::create(filename)?;
+        serde_json::to_writer_pretty(&mut file, &records)?;
+        writeln!(file)
+    }
+
+    /// Write one csv line for each run of each algorithm (on each pool of the threads
+    /// sweep and for each input size of parametrized algorithms), with its input size
+    /// (empty if not parametrized), duration, idle time, number of threads
+    /// and one column for the time spent in each tag.
+    /// Call it before `generate_logs` to get both files.
+    pub fn generate_csv<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error> {
+        let runs: Vec<_> = self.runs().collect();
+        let tags: BTreeSet<&String> = runs
+            .iter()
+            .flat_map(|(_, _, _, _, tags_times)| tags_times.keys())
+            .collect();
+        let mut file = File::create(filename)?;
+        writeln!(
+            file,
+            "algorithm,size,run,threads_number,duration,idle_time{}",
+            tags.iter()
+                .map(|tag| format!(",{}", csv_field(tag)))
+                .collect::<String>()
+        )?;
+        for (label, size, index, run, tags_times) in &runs {
+            writeln!(
+                file,
+                "{},{},{},{},{},{}{}",
+                csv_field(label),
+                size.map(|size| size.to_string()).unwrap_or_default(),
+                index,
+                run.threads_number,
+                run.duration,
+                idle_time(run),
+                tags.iter()
+                    .map(|tag| format!(",{}", tags_times.get(*tag).cloned().unwrap_or(0)))
+                    .collect::<String>()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Save the durations of all runs as a baseline for later calls to `against_baseline`.
+    /// Only runs of algorithms attached without parameters on the comparator's pool are saved:
+    /// baselines are for a given number of threads and are indexed by algorithm labels only,
+    /// so the runs of threads sweeps and of parametrized algorithms are left out.
+    pub fn save_baseline<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error> {
+        let baseline = Baseline {
+            threads_number: self.threads_number(),
+            metadata: self
+                .logs
+                .first()
+                .and_then(|runs| runs.first())
+                .map(|run| run.metadata.clone())
+                .unwrap_or_default(),
+            durations: self.labels.iter().cloned().zip(self.durations()).collect(),
+        };
+        let mut file = File::create(filename)?;
+        serde_json::to_writer_pretty(&mut file, &baseline)?;
+        writeln!(file)
+    }
+
+    /// Compare median durations of all attached algorithms with the ones saved
+    /// in given baseline (see `save_baseline`).
+    /// Algorithms whose median increased by more than the regression threshold
+    /// are reported as regressed.
+    /// Fails if the baseline was recorded on a different number of threads.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::{RegressionStatus, ThreadPoolBuilder};
+    ///
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let baseline = std::env::temp_dir().join("sum_baseline.json");
+    /// pool.compare()
+    ///     .runs_number(5)
+    ///     .attach_algorithm_nodisplay("sum", || {
+    ///         v.par_iter().sum::<u64>();
+    ///     })
+    ///     .save_baseline(&baseline)
+    ///     .expect("saving baseline failed");
+    ///
+    /// let verdict = pool
+    ///     .compare()
+    ///     .runs_number(5)
+    ///     .regression_threshold(1000.0) // we only want a working example
+    ///     .attach_algorithm_nodisplay("sum", || {
+    ///         v.par_iter().sum::<u64>();
+    ///     })
+    ///     .attach_algorithm_nodisplay("max", || {
+    ///         v.par_iter().max();
+    ///     })
+    ///     .against_baseline(&baseline)
+    ///     .expect("loading baseline failed");
+    /// println!("{}", verdict);
+    /// assert!(!verdict.has_regressions());
+    /// assert_eq!(verdict.get("max").unwrap().status, RegressionStatus::New);
+    /// ```
+    pub fn against_baseline<P: AsRef<Path>>(
+        &self,
+        filename: P,
+    ) -> Result<BaselineVerdict, crate::Error> {
+        let baseline: Baseline = serde_json::from_reader(BufReader::new(File::open(filename)?))?;
+        let statistics = Stats::get_statistics(&self.logs, self.threads_number(), self.runs_number);
+        let labels_durations = self
+            .labels
+            .iter()
+            .map(String::as_str)
+            .zip(statistics.durations_summaries())
+            .zip(self.durations())
+            .map(|((label, summary), durations)| (label, summary, durations));
+        Ok(baseline.verdict(
+            labels_durations,
+            self.threads_number(),
+            self.regression_threshold,
+        )?)
+    }
+
+    /// Number of threads we run on.
+    fn threads_number(&self) -> usize {
+        self.logs
+            .first()
+            .and_then(|runs| runs.first())
+            .map(|run| run.threads_number)
+            .unwrap_or(0)
+    }
+
+    /// Durations of all runs for each algorithm.
+    fn durations(&self) -> impl Iterator<Item = Vec<TimeStamp>> + '_ {
+        self.logs
+            .iter()
+            .map(|runs| runs.iter().map(|run| run.duration).collect())
+    }
+
+    /// Display speedup and efficiency curves and idle times for all numbers of threads.
+    /// Speedups are relative to the runs on the fewest threads (times their number).
+    fn write_scaling(&self, html_file: &mut File) -> Result<(), Error> {
+        let levels: Vec<(usize, &Vec<Vec<RunLog>>)> = self
+            .sweep_pools
+            .iter()
+            .map(|pool| pool.pool.current_num_threads())
+            .zip(self.sweep_logs.iter())
+            .chain(once((self.pool.pool.current_num_threads(), &self.logs)))
+            .sorted_by_key(|&(threads_number, _)| threads_number)
+            .collect();
+        let threads_numbers: Vec<usize> = levels.iter().map(|&(t, _)| t).collect();
+        let statistics: Vec<Stats> = levels
+            .iter()
+            .map(|&(threads_number, logs)| {
+                Stats::get_statistics(logs, threads_number, self.runs_number)
+            })
+            .collect();
+        // medians[level][algorithm]
+        let medians: Vec<Vec<f64>> = statistics
+            .iter()
+            .map(|s| s.durations_summaries().map(|d| d.median).collect())
+            .collect();
+        let speedups: Vec<Vec<f64>> = (0..self.labels.len())
+            .map(|algorithm| {
+                medians
+                    .iter()
+                    .map(|level_medians| {
+                        medians[0][algorithm] * threads_numbers[0] as f64
+                            / level_medians[algorithm].max(1.0)
+                    })
+                    .collect()
+            })
+            .collect();
+        let efficiencies: Vec<Vec<f64>> = speedups
+            .iter()
+            .map(|algorithm_speedups| {
+                algorithm_speedups
+                    .iter()
+                    .zip(&threads_numbers)
+                    .map(|(speedup, &threads_number)| speedup / threads_number as f64)
+                    .collect()
+            })
+            .collect();
+
+        writeln!(
+            html_file,
+            "<H2>Scaling on {} threads</H2>",
+            threads_numbers.iter().join(", ")
+        )?;
+        writeln!(html_file, "<H3>Speedup (median durations)</H3>")?;
+        let ideal_speedups: Vec<f64> = threads_numbers.iter().map(|&t| t as f64).collect();
+        line_chart(html_file, &threads_numbers, &speedups, &ideal_speedups)?;
+        writeln!(html_file, "<H3>Parallel efficiency</H3>")?;
+        line_chart(
+            html_file,
+            &threads_numbers,
+            &efficiencies,
+            &vec![1.0; threads_numbers.len()],
+        )?;
+        writeln!(html_file, "<H3>Idle times of median runs</H3>")?;
+        writeln!(
+            html_file,
+            "<table><tr><th></th><th>algorithm</th><th>threads</th><th>net time</th><th>idle time</th><th>searching</th><th>sleeping</th><th>speedup</th><th>efficiency</th></tr>"
+        )?;
+        for (algorithm, (name, algo_color)) in self
+            .labels
+            .iter()
+            .zip(HISTOGRAM_COLORS.iter().cycle())
+            .enumerate()
+        {
+            for (level, (level_statistics, threads_number)) in
+                statistics.iter().zip(&threads_numbers).enumerate()
+            {
+                writeln!(
+                    html_file,
+                    "<tr><td><span style='color:{}'>&#9632;</span></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.1}%</td></tr>",
+                    algo_color,
+                    name,
+                    threads_number,
+                    time_string(level_statistics.total_times_median().nth(algorithm).unwrap()),
+                    time_string(level_statistics.idle_times_median().nth(algorithm).unwrap()),
+                    time_string(
+                        level_statistics
+                            .idle_kind_times_median(IdleKind::Searching)
+                            .nth(algorithm)
+                            .unwrap()
+                    ),
+                    time_string(
+                        level_statistics
+                            .idle_kind_times_median(IdleKind::Sleeping)
+                            .nth(algorithm)
+                            .unwrap()
+                    ),
+                    speedups[algorithm][level],
+                    efficiencies[algorithm][level] * 100.0
+                )?;
+            }
+        }
+        writeln!(html_file, "</table>")
+    }
+
+    /// Display durations of parametrized algorithms against input sizes, with the sizes
+    /// at which they overtake each other and the throughput of each tag.
+    fn write_parametrized(&self, html_file: &mut File) -> Result<(), Error> {
+        // durations of median runs for each algorithm and size
+        let medians: Vec<Vec<(usize, TimeStamp)>> = self
+            .parametrized
+            .iter()
+            .map(|p| {
+                p.sizes
+                    .iter()
+                    .zip(&p.logs)
+                    .map(|(&size, runs)| (size, runs[runs.len() / 2].duration))
+                    .collect()
+            })
+            .collect();
+        let crossovers: Vec<(usize, usize, f64, usize, usize)> = (0..medians.len())
+            .tuple_combinations()
+            .flat_map(|(first, second)| {
+                crossovers(&medians[first], &medians[second])
+                    .into_iter()
+                    .map(move |(first_overtakes, size, low, high)| {
+                        let (winner, loser) = if first_overtakes {
+                            (first, second)
+                        } else {
+                            (second, first)
+                        };
+                        (winner, loser, size, low, high)
+                    })
+            })
+            .collect();
+
+        writeln!(
+            html_file,
+            "<H2>Durations against input sizes (median of {} runs)</H2>",
+            self.runs_number
+        )?;
+        for (parametrized, color) in self
+            .parametrized
+            .iter()
+            .zip(HISTOGRAM_COLORS.iter().cycle())
+        {
+            writeln!(
+                html_file,
+                "<span style='color:{}'>&#9632;</span> {} ",
+                color, parametrized.label
+            )?;
+        }
+        writeln!(html_file, "<br>")?;
+        log_log_chart(
+            html_file,
+            &medians,
+            &crossovers
+                .iter()
+                .map(|&(_, _, size, _, _)| size)
+                .collect::<Vec<_>>(),
+        )?;
+        for &(winner, loser, size, low, high) in &crossovers {
+            writeln!(
+                html_file,
+                "<p><b>{}</b> overtakes <b>{}</b> around size {:.0} (between {} and {})</p>",
+                self.parametrized[winner].label, self.parametrized[loser].label, size, low, high
+            )?;
+        }
+
+        let tags: BTreeSet<String> = self
+            .parametrized
+            .iter()
+            .flat_map(|p| p.logs.iter())
+            .flat_map(|runs| runs[runs.len() / 2].stats().into_keys())
+            .collect();
+        writeln!(
+            html_file,
+            "<H3>Throughput of tags (work amount per second)</H3>"
+        )?;
+        writeln!(
+            html_file,
+            "<table><tr><th></th><th>algorithm</th><th>size</th><th>net time</th>{}</tr>",
+            tags.iter()
+                .map(|t| format!("<th>{}</th>", t))
+                .collect::<String>()
+        )?;
+        for (parametrized, color) in self
+            .parametrized
+            .iter()
+            .zip(HISTOGRAM_COLORS.iter().cycle())
+        {
+            for (size, runs) in parametrized.sizes.iter().zip(&parametrized.logs) {
+                let median_run = &runs[runs.len() / 2];
+                let stats = median_run.stats();
+                writeln!(
+                    html_file,
+                    "<tr><td><span style='color:{}'>&#9632;</span></td><td>{}</td><td>{}</td><td>{}</td>{}</tr>",
+                    color,
+                    parametrized.label,
+                    size,
+                    time_string(median_run.duration),
+                    tags.iter()
+                        .map(|tag| match stats.get(tag) {
+                            Some(&(work, time)) if time > 0 => format!(
+                                "<td>{}</td>",
+                                throughput_string(work as f64 * 1e9 / time as f64)
+                            ),
+                            _ => "<td>-</td>".to_string(),
+                        })
+                        .collect::<String>()
+                )?;
+            }
+        }
+        writeln!(html_file, "</table>")
+    }
+
     /// This method should be called in the end to write the logs to a desired html file.
     pub fn generate_logs<P: AsRef<Path>>(mut self, filename: P) -> Result<(), Error> {
+        if let Some(misuse) = self.misuse {
+            return Err(Error::new(ErrorKind::InvalidInput, misuse));
+        }
         let tags = self.fuse_tags(); // have a consistent tags numbering accross all logs
         let mut html_file = File::create(filename)?;
 
@@ -169,13 +734,24 @@ table, th, td {{
 </head>
 <body><center>"#,
         )?;
-        let (last_label, first_labels) = self.labels.split_last().expect("not enough experiments");
+        let labels: Vec<&String> = self
+            .labels
+            .iter()
+            .chain(self.parametrized.iter().map(|p| &p.label))
+            .collect();
+        let (last_label, first_labels) = labels.split_last().expect("not enough experiments");
         writeln!(
             html_file,
             "<H1> Comparing {} and {}</H1>",
-            first_labels.join(", "),
+            first_labels.iter().join(", "),
             last_label
         )?;
+        if !self.parametrized.is_empty() {
+            self.write_parametrized(&mut html_file)?;
+        }
+        if self.logs.is_empty() {
+            return write!(html_file, "</body></html>");
+        }
 
         writeln!(
             html_file,
@@ -191,61 +767,158 @@ table, th, td {{
         }
         writeln!(html_file, "</H2>")?;
         histogram(&mut html_file, &self.logs, 30)?;
+        if !self.sweep_pools.is_empty() {
+            self.write_scaling(&mut html_file)?;
+        }
         let number_of_threads = self.logs[0][0].threads_number;
         let statistics = Stats::get_statistics(&self.logs, number_of_threads, self.runs_number);
+        let summaries: Vec<_> = statistics.durations_summaries().collect();
+        let p_values = statistics.pairwise_p_values();
+        // medians are marked when significantly slower than the fastest algorithm
+        let fastest = (0..summaries.len())
+            .min_by(|&a, &b| {
+                summaries[a]
+                    .median
+                    .partial_cmp(&summaries[b].median)
+                    .unwrap()
+            })
+            .unwrap();
         writeln!(html_file, "<H2> The Mean statistics are</H2>")?;
         writeln!(
             html_file,
-            "<table><tr><th></th><th>algorithm</th><th>net time</th>{}<th>idle time</th></tr>",
+            "<table><tr><th></th><th>algorithm</th><th>net time</th><th>95% CI</th><th>std dev</th>{}<th>idle time</th><th>searching</th><th>sleeping</th><th>work</th><th>span</th><th>parallelism</th><th>speedup</th></tr>",
             tags.iter()
                 .map(|t| format!("<th>{}</th>", t))
                 .collect::<String>()
         )?;
-        for (name, total_time, tagged_columns, idle_time, algo_color) in izip!(
+        for (
+            name,
+            total_time,
+            tagged_columns,
+            idle_time,
+            searching_time,
+            sleeping_time,
+            work_span,
+            summary,
+            algo_color,
+        ) in izip!(
             //for (name, total_time, sequential_times, idle_time, algo_color) in izip!(
             self.labels.iter(),
             statistics.total_times(),
             statistics.average_tagged_times(&tags),
             statistics.idle_times(),
+            statistics.idle_kind_times(IdleKind::Searching),
+            statistics.idle_kind_times(IdleKind::Sleeping),
+            statistics.work_spans(),
+            &summaries,
             HISTOGRAM_COLORS.iter().cycle()
         ) {
             writeln!(
                 html_file,
-                "<tr><td>{}</td><td>{}</td><td>{}</td>{}<td>{}</td></tr>",
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>{}<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td></tr>",
                 format!("<span style='color:{}'>&#9632;</span>", algo_color),
                 name,
                 time_string(total_time),
+                interval_string(summary.mean_interval),
+                time_string(summary.std_dev as u64),
                 tagged_columns,
-                time_string(idle_time)
+                time_string(idle_time),
+                time_string(searching_time),
+                time_string(sleeping_time),
+                time_string(work_span.work),
+                time_string(work_span.span),
+                work_span.parallelism(),
+                work_span.speedup()
             )?;
         }
         writeln!(html_file, "</table>",)?;
         writeln!(html_file, "<H2> The Median statistics are</H2>")?;
         writeln!(
             html_file,
-            "<table><tr><th></th><th>algorithm</th><th>net time</th>{}<th>idle time</th></tr>",
+            "<table><tr><th></th><th>algorithm</th><th>net time</th><th>95% CI</th><th>MAD</th>{}<th>idle time</th><th>searching</th><th>sleeping</th><th>work</th><th>span</th><th>parallelism</th><th>speedup</th></tr>",
             tags.iter()
                 .map(|t| format!("<th>{}</th>", t))
                 .collect::<String>()
         )?;
-        for (name, total_time, tagged_columns, idle_time, algo_color) in izip!(
+        for (
+            index,
+            (
+                name,
+                total_time,
+                tagged_columns,
+                idle_time,
+                searching_time,
+                sleeping_time,
+                work_span,
+                summary,
+                algo_color,
+            ),
+        ) in izip!(
             self.labels.iter(),
             statistics.total_times_median(),
             statistics.median_tagged_times(&tags),
             statistics.idle_times_median(),
+            statistics.idle_kind_times_median(IdleKind::Searching),
+            statistics.idle_kind_times_median(IdleKind::Sleeping),
+            statistics.work_spans_median(),
+            &summaries,
             HISTOGRAM_COLORS.iter().cycle()
-        ) {
+        )
+        .enumerate()
+        {
             writeln!(
                 html_file,
-                "<tr><td>{}</td><td>{}</td><td>{}</td>{}<td>{}</td></tr>",
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>{}<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td></tr>",
                 format!("<span style='color:{}'>&#9632;</span>", algo_color),
                 name,
-                time_string(total_time),
+                format!(
+                    "{}{}",
+                    time_string(total_time),
+                    significance_marker(p_values[index][fastest])
+                ),
+                interval_string(summary.median_interval),
+                time_string(summary.mad as u64),
                 tagged_columns,
-                time_string(idle_time)
+                time_string(idle_time),
+                time_string(searching_time),
+                time_string(sleeping_time),
+                time_string(work_span.work),
+                time_string(work_span.span),
+                work_span.parallelism(),
+                work_span.speedup()
             )?;
         }
         writeln!(html_file, "</table>",)?;
+        writeln!(
+            html_file,
+            "<p>* p &lt; 0.05, ** p &lt; 0.01, *** p &lt; 0.001: significantly slower than {}</p>",
+            self.labels[fastest]
+        )?;
+        if self.labels.len() > 1 {
+            writeln!(
+                html_file,
+                "<H2> Pairwise Mann-Whitney U tests (p-values)</H2>"
+            )?;
+            writeln!(
+                html_file,
+                "<table><tr><th></th>{}</tr>",
+                self.labels
+                    .iter()
+                    .map(|l| format!("<th>{}</th>", l))
+                    .collect::<String>()
+            )?;
+            for (label, line) in self.labels.iter().zip(&p_values) {
+                writeln!(
+                    html_file,
+                    "<tr><th>{}</th>{}</tr>",
+                    label,
+                    line.iter()
+                        .map(|&p| format!("<td>{:.4}{}</td>", p, significance_marker(p)))
+                        .collect::<String>()
+                )?;
+            }
+            writeln!(html_file, "</table>",)?;
+        }
         if self.display_preferences.iter().any(|b| *b) {
             writeln!(html_file, "<H2>Comparing median runs</H2>")?;
             let median_index = (self.runs_number) / 2;
@@ -253,7 +926,7 @@ table, th, td {{
                 if self.display_preferences[pos] {
                     let scene = visualisation(&log[median_index]);
                     writeln!(html_file, "<H3 align=\"left\"><u>{}</u> :</H3>", name)?;
-                    fill_svg_file(&scene, &mut html_file)?;
+                    fill_svg_file(&scene, &mut html_file, &SvgOptions::default())?;
                     writeln!(html_file, "<p>")?;
                 }
             }
@@ -263,7 +936,7 @@ table, th, td {{
                 if self.display_preferences[pos] {
                     let scene = visualisation(&log[0]);
                     writeln!(html_file, "<H3 align=\"left\"><u>{}</u> :</H3>", name)?;
-                    fill_svg_file(&scene, &mut html_file)?;
+                    fill_svg_file(&scene, &mut html_file, &SvgOptions::default())?;
                     writeln!(html_file, "<p>")?;
                 }
             }
@@ -273,6 +946,70 @@ table, th, td {{
     }
 }
 
+/// Run given function the given number of times, returning logs sorted by duration.
+fn record_runs<F: FnMut() -> RunLog>(runs_number: usize, run_function: F) -> Vec<RunLog> {
+    let mut experiments_logs: Vec<_> = repeat_with(run_function).take(runs_number).collect();
+    experiments_logs.sort_unstable_by_key(|run| run.duration);
+    experiments_logs
+}
+
+/// Find where the first durations curve crosses the second one.
+/// Return for each crossing whether the first algorithm becomes the fastest,
+/// the estimated size (interpolating on logarithmic scales) and the closest measured sizes.
+fn crossovers(
+    first: &[(usize, TimeStamp)],
+    second: &[(usize, TimeStamp)],
+) -> Vec<(bool, f64, usize, usize)> {
+    let second: HashMap<usize, TimeStamp> = second.iter().cloned().collect();
+    // log of duration ratios on common sizes
+    let ratios: Vec<(usize, f64)> = first
+        .iter()
+        .filter_map(|&(size, duration)| {
+            second.get(&size).map(|&other| {
+                (
+                    size,
+                    (duration.max(1) as f64).ln() - (other.max(1) as f64).ln(),
+                )
+            })
+        })
+        .collect();
+    ratios
+        .iter()
+        .tuple_windows()
+        .filter(|((_, before), (_, after))| before * after < 0.0)
+        .map(|(&(low, before), &(high, after))| {
+            let t = before / (before - after);
+            let size = ((low as f64).ln() + t * ((high as f64).ln() - (low as f64).ln())).exp();
+            (after < 0.0, size, low, high)
+        })
+        .collect()
+}
+
+/// Display a number of work units per second.
+fn throughput_string(throughput: f64) -> String {
+    match throughput {
+        t if t < 1e3 => format!("{:.1}/s", t),
+        t if t < 1e6 => format!("{:.1}K/s", t / 1e3),
+        t if t < 1e9 => format!("{:.1}M/s", t / 1e6),
+        t => format!("{:.1}G/s", t / 1e9),
+    }
+}
+
+/// Time spent by all threads outside of tasks.
+fn idle_time(run: &RunLog) -> u64 {
+    (run.duration * run.threads_number as u64)
+        .saturating_sub(run.tasks_logs.iter().map(|t| t.duration()).sum())
+}
+
+/// Display a confidence interval on durations.
+fn interval_string((low, high): (f64, f64)) -> String {
+    format!(
+        "[{}, {}]",
+        time_string(low as u64),
+        time_string(high as u64)
+    )
+}
+
 pub(crate) fn time_string(nano: u64) -> String {
     match nano {
         n if n < 1_000 => format!("{}ns", n),
@@ -282,3 +1019,118 @@ pub(crate) fn time_string(nano: u64) -> String {
         n => format!("{}m{}s", n / 60_000_000_000, n % 60_000_000_000),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one task log with given tags, the task being in a subgraph tagged by the last one.
+    fn tagged_log(tags: &[&str]) -> RunLog {
+        let mut log = RunLog::from_tasks(1, &[(0, 10, 0, &[])]);
+        log.tags = tags.iter().map(|t| t.to_string()).collect();
+        log.subgraphs = vec![(0, 0, tags.len() - 1, 1)];
+        log.subgraphs_parents = vec![None];
+        log
+    }
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .expect("building pool failed")
+    }
+
+    #[test]
+    fn tags_are_fused_across_all_logs() {
+        let pool = pool();
+        let mut comparator = pool.compare();
+        comparator.logs = vec![vec![tagged_log(&["a"])]];
+        comparator.sweep_logs = vec![vec![vec![tagged_log(&["b", "a"])]]];
+        comparator.parametrized.push(Parametrized {
+            label: "p".to_string(),
+            sizes: vec![1],
+            logs: vec![vec![tagged_log(&["c"])]],
+        });
+        assert_eq!(comparator.fuse_tags(), vec!["a", "b", "c"]);
+        let sweep_log = &comparator.sweep_logs[0][0][0];
+        assert_eq!(sweep_log.tags, vec!["a", "b", "c"]);
+        assert_eq!(sweep_log.subgraphs[0].2, 0);
+        assert_eq!(comparator.parametrized[0].logs[0][0].subgraphs[0].2, 2);
+    }
+
+    #[test]
+    fn sweep_and_parametrized_runs_are_exported() {
+        let pool = pool();
+        let mut comparator = pool.compare();
+        comparator.labels = vec!["sum".to_string()];
+        comparator.logs = vec![vec![tagged_log(&["a"])]];
+        let mut sweep_log = RunLog::from_tasks(2, &[(0, 5, 0, &[])]);
+        sweep_log.tags = vec!["b".to_string()];
+        sweep_log.subgraphs = vec![(0, 0, 0, 1)];
+        sweep_log.subgraphs_parents = vec![None];
+        comparator.sweep_logs = vec![vec![vec![sweep_log]]];
+        comparator.parametrized.push(Parametrized {
+            label: "sum".to_string(),
+            sizes: vec![100],
+            logs: vec![vec![tagged_log(&["a"])]],
+        });
+        let path = std::env::temp_dir().join("rayon_logs_exported_runs.csv");
+        comparator.generate_csv(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).ok();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "algorithm,size,run,threads_number,duration,idle_time,a,b",
+                "sum,,0,1,10,0,10,0",
+                "sum,,0,2,5,5,0,5",
+                "sum,100,0,1,10,0,10,0",
+            ]
+        );
+    }
+
+    #[test]
+    fn crossovers_are_interpolated() {
+        let first = [(10, 100), (100, 500), (1_000, 5_000), (10_000, 20_000)];
+        let second = [
+            (10, 200),
+            (100, 400),
+            (1_000, 2_000),
+            (5_000, 1),
+            (10_000, 40_000),
+        ];
+        let found = crossovers(&first, &second);
+        assert_eq!(found.len(), 2);
+        // first becomes slower between 10 and 100 then faster again between 1000 and 10000
+        let (first_overtakes, size, low, high) = found[0];
+        assert_eq!((first_overtakes, low, high), (false, 10, 100));
+        let expected = 10f64.powf(1.0 + 2f64.ln() / 2.5f64.ln());
+        assert!((size - expected).abs() < 1e-6, "crossing at {}", size);
+        let (first_overtakes, size, low, high) = found[1];
+        assert_eq!((first_overtakes, low, high), (true, 1_000, 10_000));
+        assert!(1_000.0 < size && size < 10_000.0);
+        assert!(crossovers(&first, &first).is_empty());
+        assert!(crossovers(&first, &[]).is_empty());
+    }
+
+    #[test]
+    fn throughputs() {
+        assert_eq!(throughput_string(12.34), "12.3/s");
+        assert_eq!(throughput_string(12_340.0), "12.3K/s");
+        assert_eq!(throughput_string(2.5e9), "2.5G/s");
+    }
+
+    #[test]
+    fn late_sweeps_are_rejected() {
+        let pool = pool();
+        let error = pool
+            .compare()
+            .runs_number(1)
+            .attach_algorithm_nodisplay("nothing", || ())
+            .threads_sweep(&[2])
+            .generate_logs(std::env::temp_dir().join("rayon_logs_late_sweep.html"))
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+}
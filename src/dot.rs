@@ -0,0 +1,116 @@
+//! Export logs as graphviz DOT files.
+//! Small traces are often easier to understand in a classic dag layout and DOT files
+//! can be fed to other graph tools.
+use crate::compare::time_string;
+use crate::log::RunLog;
+use crate::svg::COLORS;
+use std::io::{self, Write};
+
+/// Escape given string for a DOT quoted string.
+fn quoted(label: &str) -> String {
+    format!(
+        "\"{}\"",
+        label
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Return the (semi transparent) fill color of given thread.
+fn thread_color(thread: usize) -> String {
+    let [r, g, b] = COLORS[thread % COLORS.len()];
+    format!(
+        "#{:02x}{:02x}{:02x}80",
+        (r * 255.0) as u8,
+        (g * 255.0) as u8,
+        (b * 255.0) as u8
+    )
+}
+
+/// Write given log's tasks graph in DOT format.
+/// Nodes are tasks, colored by thread, and nested subgraphs are nested clusters labelled by
+/// their tags. Dependencies between tasks on different threads are dashed.
+pub(crate) fn write_dot<W: Write>(log: &RunLog, writer: &mut W) -> io::Result<()> {
+    let innermost_subgraphs = log.innermost_subgraphs();
+    let parents = &log.subgraphs_parents;
+    // tasks directly inside each subgraph (last one is for tasks outside of all subgraphs)
+    let mut clusters_tasks = vec![Vec::new(); log.subgraphs.len() + 1];
+    for (task, innermost) in innermost_subgraphs.iter().enumerate() {
+        clusters_tasks[innermost.unwrap_or(log.subgraphs.len())].push(task);
+    }
+    let mut clusters_children = vec![Vec::new(); log.subgraphs.len() + 1];
+    for (subgraph, parent) in parents.iter().enumerate() {
+        clusters_children[parent.unwrap_or(log.subgraphs.len())].push(subgraph);
+    }
+
+    writeln!(writer, "digraph tasks {{")?;
+    writeln!(writer, "  node [shape=box, style=filled];")?;
+    write_cluster(
+        log,
+        writer,
+        log.subgraphs.len(),
+        &clusters_tasks,
+        &clusters_children,
+        1,
+    )?;
+    for (task_id, task) in log.tasks_logs.iter().enumerate() {
+        for &child in &task.children {
+            if log.tasks_logs[child].thread_id == task.thread_id {
+                writeln!(writer, "  t{} -> t{};", task_id, child)?;
+            } else {
+                writeln!(writer, "  t{} -> t{} [style=dashed];", task_id, child)?;
+            }
+        }
+    }
+    writeln!(writer, "}}")
+}
+
+/// Write given cluster's tasks and (recursively) its sub-clusters.
+/// The last cluster is the whole graph.
+fn write_cluster<W: Write>(
+    log: &RunLog,
+    writer: &mut W,
+    cluster: usize,
+    clusters_tasks: &[Vec<usize>],
+    clusters_children: &[Vec<usize>],
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for &task_id in &clusters_tasks[cluster] {
+        let task = &log.tasks_logs[task_id];
+        writeln!(
+            writer,
+            "{}t{} [label={}, fillcolor={}];",
+            indent,
+            task_id,
+            quoted(&format!(
+                "task {}\nthread {}\n{}",
+                task_id,
+                task.thread_id,
+                time_string(task.duration())
+            )),
+            quoted(&thread_color(task.thread_id))
+        )?;
+    }
+    for &subgraph in &clusters_children[cluster] {
+        let (_, _, tag, work_amount) = log.subgraphs[subgraph];
+        writeln!(writer, "{}subgraph cluster_{} {{", indent, subgraph)?;
+        writeln!(
+            writer,
+            "{}  label={};",
+            indent,
+            quoted(&format!("{} ({})", log.tags[tag], work_amount))
+        )?;
+        write_cluster(
+            log,
+            writer,
+            subgraph,
+            clusters_tasks,
+            clusters_children,
+            depth + 1,
+        )?;
+        writeln!(writer, "{}}}", indent)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Errors reported when loading or building logs.
+use crate::raw_events::TaskId;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Everything which can go wrong when loading, saving or building logs.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed (binary logs with invalid content also end up here).
+    Io(io::Error),
+    /// A json log file could not be parsed.
+    Parse {
+        /// line of the error (starting at 1, 0 if unknown)
+        line: usize,
+        /// column of the error (starting at 1, 0 if unknown)
+        column: usize,
+        /// what went wrong
+        message: String,
+    },
+    /// The log was saved with a schema version we do not know about.
+    UnsupportedSchema(u64),
+    /// Logged events do not form a valid tasks graph.
+    /// This happens for example when mixing logged and un-logged computations.
+    Inconsistent {
+        /// offending task (if known)
+        task: Option<TaskId>,
+        /// thread which logged the offending event
+        thread: usize,
+        /// what went wrong
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {}", e),
+            Error::Parse {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "parse error at line {} column {}: {}",
+                line, column, message
+            ),
+            Error::UnsupportedSchema(version) => write!(
+                f,
+                "log schema version {} is newer than supported version {}",
+                version,
+                crate::SCHEMA_VERSION
+            ),
+            Error::Inconsistent {
+                task: Some(task),
+                thread,
+                reason,
+            } => write!(
+                f,
+                "inconsistent logs for task {} on thread {}: {}",
+                task, thread, reason
+            ),
+            Error::Inconsistent {
+                task: None,
+                thread,
+                reason,
+            } => write!(f, "inconsistent logs on thread {}: {}", thread, reason),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            Error::Io(error.into())
+        } else {
+            // serde appends the position to its messages, we store it apart
+            let position = format!(" at line {} column {}", error.line(), error.column());
+            let message = error.to_string();
+            Error::Parse {
+                line: error.line(),
+                column: error.column(),
+                message: message
+                    .strip_suffix(&position)
+                    .unwrap_or(&message)
+                    .to_string(),
+            }
+        }
+    }
+}
+
+impl Error {
+    /// Logged events are inconsistent.
+    pub(crate) fn inconsistent(task: Option<TaskId>, thread: usize, reason: &'static str) -> Self {
+        Error::Inconsistent {
+            task,
+            thread,
+            reason,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Flamegraph exports of nested subgraphs.
+//! Each task gets the stack of tags of all subgraphs containing it (outermost first).
+//! We export folded stacks (for flamegraph.pl or inferno) and speedscope json
+//! (https://www.speedscope.app).
+use crate::log::RunLog;
+use crate::raw_events::TimeStamp;
+use itertools::Itertools;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::iter::once;
+
+/// Frame used for tasks outside of all subgraphs.
+const NO_TAGS: &str = "_NO_TAGS_";
+
+/// Return the stack of each task, tasks outside of all subgraphs get the `_NO_TAGS_` frame.
+fn tasks_stacks(log: &RunLog) -> Vec<Vec<&str>> {
+    log.tasks_tag_stacks()
+        .into_iter()
+        .map(|stack| {
+            if stack.is_empty() {
+                vec![NO_TAGS]
+            } else {
+                stack
+            }
+        })
+        .collect()
+}
+
+/// Sum the durations of all tasks for each stack.
+/// Stacks are joined by ';' and sorted.
+pub(crate) fn folded_stacks(log: &RunLog) -> Vec<(String, TimeStamp)> {
+    let mut durations: HashMap<String, TimeStamp> = HashMap::new();
+    for (task, stack) in log.tasks_logs.iter().zip(tasks_stacks(log)) {
+        *durations.entry(stack.join(";")).or_default() += task.duration();
+    }
+    durations.into_iter().sorted().collect()
+}
+
+/// Write folded stacks, one "outer;inner count" line per stack.
+/// Counts are durations in nanoseconds.
+pub(crate) fn write_folded_stacks<W: Write>(log: &RunLog, writer: &mut W) -> io::Result<()> {
+    for (stack, duration) in folded_stacks(log) {
+        writeln!(writer, "{} {}", stack, duration)?;
+    }
+    Ok(())
+}
+
+/// Write given log as a speedscope file with one evented profile per thread.
+/// Frames are opened when a task starts and closed when it ends.
+pub(crate) fn write_speedscope<W: Write>(log: &RunLog, writer: &mut W) -> io::Result<()> {
+    let frames: Vec<&str> = log
+        .tags
+        .iter()
+        .map(String::as_str)
+        .chain(once(NO_TAGS))
+        .collect();
+    let frames_indices: HashMap<&str, usize> =
+        frames.iter().enumerate().map(|(i, &f)| (f, i)).collect();
+    let frames_indices = &frames_indices;
+    let stacks = tasks_stacks(log);
+    let threads_tasks = (0..log.tasks_logs.len())
+        .map(|task| (log.tasks_logs[task].thread_id, task))
+        .into_group_map();
+    // events use absolute times: profiles end with the last task
+    let end_value = log.tasks_logs.iter().map(|t| t.end_time).max().unwrap_or(0);
+    let profiles: Vec<_> = (0..log.threads_number)
+        .map(|thread| {
+            let events: Vec<_> = threads_tasks
+                .get(&thread)
+                .into_iter()
+                .flatten()
+                .sorted_by_key(|&&task| (log.tasks_logs[task].start_time, task))
+                .flat_map(|&task| {
+                    let stack = &stacks[task];
+                    let (start, end) = (
+                        log.tasks_logs[task].start_time,
+                        log.tasks_logs[task].end_time,
+                    );
+                    let opened = stack.iter().map(
+                        move |f| json!({"type": "O", "frame": frames_indices[f], "at": start}),
+                    );
+                    let closed = stack
+                        .iter()
+                        .rev()
+                        .map(move |f| json!({"type": "C", "frame": frames_indices[f], "at": end}));
+                    opened.chain(closed).collect::<Vec<_>>()
+                })
+                .collect();
+            json!({
+                "type": "evented",
+                "name": format!("thread {}", thread),
+                "unit": "nanoseconds",
+                "startValue": 0,
+                "endValue": end_value,
+                "events": events,
+            })
+        })
+        .collect();
+    let file = json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "shared": {"frames": frames.iter().map(|f| json!({"name": f})).collect::<Vec<_>>()},
+        "profiles": profiles,
+        "name": "rayon_logs",
+        "exporter": format!("rayon_logs {}", log.metadata.crate_version),
+    });
+    serde_json::to_writer(&mut *writer, &file)?;
+    writer.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn speedscope_profiles_cover_all_events() {
+        // a (partial) history not starting at 0
+        let mut log = RunLog::from_tasks(1, &[(100, 150, 0, &[1]), (150, 180, 0, &[])]);
+        log.tags = vec!["sum".to_string()];
+        log.subgraphs = vec![(0, 0, 0, 10)];
+        log.subgraphs_parents = vec![None];
+        log.partial = true;
+        assert_eq!(
+            folded_stacks(&log),
+            vec![(NO_TAGS.to_string(), 30), ("sum".to_string(), 50)]
+        );
+        let mut bytes = Vec::new();
+        write_speedscope(&log, &mut bytes).unwrap();
+        let file: Value = serde_json::from_slice(&bytes).unwrap();
+        let profile = &file["profiles"][0];
+        let last_event = profile["events"]
+            .as_array()
+            .unwrap()
+            .last()
+            .unwrap()
+            .clone();
+        assert_eq!(last_event["at"], 180);
+        assert_eq!(profile["endValue"], 180);
+    }
+}
@@ -1,13 +1,14 @@
 //! Store a trace as a fork join graph (in a vector).
+use crate::compare::time_string;
 use crate::raw_events::{TaskId, TimeStamp};
 use crate::svg::{Rectangle, Scene, COLORS};
 type BlockId = usize;
-use crate::log::{RunLog, TaskLog};
+use crate::log::{idle_gaps, IdleKind, RunLog, TaskLog};
 use itertools::{iproduct, Itertools};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::{once, repeat};
 
-const VERTICAL_GAP: f64 = 0.2;
+pub(crate) const VERTICAL_GAP: f64 = 0.2;
 
 use crate::svg::Point;
 
@@ -256,6 +257,8 @@ fn compute_positions(
 
 /// Take a block ; fill its rectangles and edges and return a set of entry points for incoming edges
 /// and a set of exit points for outgoing edges.
+/// Collapsed blocks are displayed as one summary rectangle.
+#[allow(clippy::too_many_arguments)]
 fn generate_visualisation(
     index: BlockId,
     graph: &Vec<Block>,
@@ -264,7 +267,19 @@ fn generate_visualisation(
     tasks_information: &mut HashMap<TaskId, HashMap<String, (String, f64)>>,
     tags: &[String],
     blocks_dimensions: &[(f64, f64)],
+    collapsed: &HashSet<BlockId>,
 ) -> (Vec<Point>, Vec<Point>) {
+    if collapsed.contains(&index) {
+        return generate_summary(
+            index,
+            graph,
+            positions,
+            scene,
+            tasks_information,
+            tags,
+            blocks_dimensions,
+        );
+    }
     match graph[index] {
         Block::Sequence(ref s) => {
             let points: Vec<(Vec<Point>, Vec<Point>)> = s
@@ -278,6 +293,7 @@ fn generate_visualisation(
                         tasks_information,
                         tags,
                         blocks_dimensions,
+                        collapsed,
                     )
                 })
                 .collect();
@@ -300,6 +316,7 @@ fn generate_visualisation(
                 tasks_information,
                 tags,
                 blocks_dimensions,
+                collapsed,
             );
             acc.0.extend(entry);
             acc.1.extend(exit);
@@ -308,13 +325,15 @@ fn generate_visualisation(
         Block::Task(task_id, ref t) => {
             let duration = (t.end_time - t.start_time) as f64;
             let information = tasks_information.remove(&task_id).unwrap();
-            scene.rectangles.push(Rectangle::new(
+            let mut rectangle = Rectangle::new(
                 COLORS[t.thread_id % COLORS.len()],
                 positions[index],
                 (duration, 1.0),
                 (t.start_time, t.end_time),
                 information,
-            ));
+            );
+            rectangle.task = Some(task_id);
+            scene.rectangles.push(rectangle);
             (
                 vec![(positions[index].0 + duration / 2.0, positions[index].1)],
                 vec![(
@@ -326,40 +345,191 @@ fn generate_visualisation(
     }
 }
 
-/// Take all taskslogs and compute idle periods animations for each thread.
+/// Return all idle periods to display as (thread, start, end, label, opacity).
+pub(crate) fn displayed_idle_periods(log: &RunLog) -> Vec<(usize, u64, u64, &'static str, f64)> {
+    // logs from older versions (or from a rayon without idle hooks) do not know what idle
+    // threads were doing
+    if log.idle_periods.is_empty() {
+        idle_gaps(&log.tasks_logs, log.threads_number)
+            .into_iter()
+            .map(|(thread_id, start, end)| (thread_id, start, end, "idle", 1.0))
+            .collect()
+    } else {
+        log.idle_periods
+            .iter()
+            .map(|p| {
+                let opacity = match p.kind {
+                    IdleKind::Searching => 1.0,
+                    IdleKind::Sleeping => 0.4,
+                };
+                (p.thread_id, p.start, p.end, p.kind.label(), opacity)
+            })
+            .collect()
+    }
+}
+
+/// Display given block as a single rectangle summarizing all its tasks.
+/// The detailed display is kept inside the summary for interactive viewers.
+fn generate_summary(
+    index: BlockId,
+    graph: &Vec<Block>,
+    positions: &[(f64, f64)],
+    scene: &mut Scene,
+    tasks_information: &mut HashMap<TaskId, HashMap<String, (String, f64)>>,
+    tags: &[String],
+    blocks_dimensions: &[(f64, f64)],
+) -> (Vec<Point>, Vec<Point>) {
+    // generate the details in an empty scene
+    let rectangles = std::mem::take(&mut scene.rectangles);
+    let segments = std::mem::take(&mut scene.segments);
+    generate_visualisation(
+        index,
+        graph,
+        positions,
+        scene,
+        tasks_information,
+        tags,
+        blocks_dimensions,
+        &HashSet::new(),
+    );
+    let details = std::mem::replace(&mut scene.rectangles, rectangles);
+    let details_segments = std::mem::replace(&mut scene.segments, segments);
+
+    let mut tasks = Vec::new();
+    block_tasks(index, graph, &mut tasks);
+    let work: u64 = tasks.iter().map(|t| t.duration()).sum();
+    let threads: Vec<usize> = tasks.iter().map(|t| t.thread_id).sorted().dedup().collect();
+    let start = tasks.iter().map(|t| t.start_time).min().unwrap_or(0);
+    let end = tasks.iter().map(|t| t.end_time).max().unwrap_or(0);
+    let label = format!(
+        "summary of {} tasks\nwork: {} (micro sec)\nthreads: {}",
+        tasks.len(),
+        work / 1000,
+        threads.iter().join(", ")
+    );
+    // each tag gets the work weighted opacity of the tasks displaying it
+    let mut opacities: HashMap<&String, (f64, f64)> = HashMap::new();
+    for rectangle in &details {
+        let weight = rectangle.width.max(1.0);
+        for (tag, (_, opacity)) in &rectangle.information {
+            let entry = opacities.entry(tag).or_insert((0.0, 0.0));
+            entry.0 += opacity * weight;
+            entry.1 += weight;
+        }
+    }
+    let information = opacities
+        .into_iter()
+        .map(|(tag, (opacity, weight))| (tag.clone(), (label.clone(), opacity / weight)))
+        .collect();
+    let color = if threads.len() == 1 {
+        COLORS[threads[0] % COLORS.len()]
+    } else {
+        [0.5, 0.5, 0.5]
+    };
+    let (x, y) = positions[index];
+    let (width, height) = blocks_dimensions[index];
+    let mut summary = Rectangle::new(color, (x, y), (width, height), (start, end), information);
+    summary.details = Some((details, details_segments));
+    scene.rectangles.push(summary);
+    (
+        vec![(x + width / 2.0, y)],
+        vec![(x + width / 2.0, y + height)],
+    )
+}
+
+/// Collect all tasks inside given block.
+fn block_tasks<'a>(index: BlockId, graph: &'a [Block], tasks: &mut Vec<&'a TaskLog>) {
+    match graph[index] {
+        Block::Sequence(ref b) | Block::Parallel(ref b) => {
+            for sub_block in b {
+                block_tasks(*sub_block, graph, tasks)
+            }
+        }
+        Block::Task(_, ref t) => tasks.push(t),
+    }
+}
+
+/// Total duration of all tasks inside each block.
+fn compute_blocks_work(index: BlockId, graph: &[Block], blocks_work: &mut [u64]) -> u64 {
+    let work = match graph[index] {
+        Block::Sequence(ref b) | Block::Parallel(ref b) => b
+            .iter()
+            .map(|sub_block| compute_blocks_work(*sub_block, graph, blocks_work))
+            .sum(),
+        Block::Task(_, ref t) => t.duration(),
+    };
+    blocks_work[index] = work;
+    work
+}
+
+/// Return all blocks to collapse, taking the outermost ones.
+/// Tasks and blocks containing only one task are never collapsed.
+fn collapsed_blocks(
+    roots: &[BlockId],
+    graph: &[Block],
+    aggregation: Aggregation,
+    blocks_dimensions: &[(f64, f64)],
+    graph_width: f64,
+) -> HashSet<BlockId> {
+    let mut blocks_work = vec![0; graph.len()];
+    for root in roots {
+        compute_blocks_work(*root, graph, &mut blocks_work);
+    }
+    let small = |block: BlockId| match aggregation {
+        Aggregation::Duration(duration) => blocks_work[block] < duration,
+        Aggregation::Pixels(pixels) => {
+            blocks_dimensions[block].0 * DISPLAY_WIDTH / graph_width < pixels
+        }
+    };
+    let mut collapsed = HashSet::new();
+    let mut stack: Vec<BlockId> = roots.to_vec();
+    while let Some(block) = stack.pop() {
+        let sub_blocks = match graph[block] {
+            Block::Sequence(ref b) | Block::Parallel(ref b) => b,
+            Block::Task(..) => continue,
+        };
+        let single_task = sub_blocks.len() == 1 && matches!(graph[sub_blocks[0]], Block::Task(..));
+        if small(block) && !single_task && !roots.contains(&block) {
+            collapsed.insert(block);
+        } else {
+            stack.extend(sub_blocks);
+        }
+    }
+    collapsed
+}
+
+/// Start, end, summed idle time, number of periods, label and opacity (weighted by duration)
+/// of merged periods.
+/// Threads might be busy between merged periods so the idle time is not end - start.
+type MergedIdlePeriods = (u64, u64, u64, usize, &'static str, f64);
+
+/// Take all idle periods and compute their animations for each thread.
 /// add all rectangles to given vector.
 /// given height (height of animated running tasks) enables us to center the display vertically.
 /// y is vertical start for this log.
+/// Consecutive periods of a thread are merged until they are at least `min_width` wide.
 fn compute_idle_times(
-    tasks: &[TaskLog],
+    log: &RunLog,
     starting_position: &(f64, f64),
-    threads_number: usize,
     scene: &mut Scene,
+    min_width: f64,
 ) {
-    // do one pass to figure out the last recorded time.
-    // we need it to figure out who is idle at the end.
-    let last_time = tasks.iter().map(|t| t.end_time).max().unwrap();
-    let first_time = tasks.iter().map(|t| t.start_time).min().unwrap();
-
-    // sort everyone by time (yes i know, again).
-    // we add fake tasks at the end for last idle periods.
-    let mut sorted_tasks: Vec<(usize, TimeStamp, TimeStamp)> = tasks
-        .iter()
-        .map(|t| (t.thread_id, t.start_time, t.end_time))
-        .chain((0..threads_number).map(|i| (i, last_time, last_time + 1)))
-        .collect();
-
-    sorted_tasks.sort_by(|t1, t2| t1.1.partial_cmp(&t2.1).unwrap());
+    let threads_number = log.threads_number;
+    let idle_periods = displayed_idle_periods(log);
 
-    let mut previous_activities: Vec<TimeStamp> = repeat(first_time).take(threads_number).collect();
     let mut current_x_positions: Vec<f64> =
         repeat(starting_position.0).take(threads_number).collect();
-
-    // replay execution, figuring out idle times
-    for (thread_id, start, end) in sorted_tasks {
-        let previous_end = previous_activities[thread_id];
-        if start > previous_end {
-            let inactivity = (start - previous_end) as f64;
+    // for each thread, periods merged so far
+    let mut pending: Vec<Option<MergedIdlePeriods>> = vec![None; threads_number];
+
+    let mut push_idle =
+        |thread_id: usize, (start, end, idle, count, label, opacity): MergedIdlePeriods| {
+            let inactivity = idle as f64;
+            let label = if count == 1 {
+                label.to_string()
+            } else {
+                format!("{} idle periods ({} idle)", count, time_string(idle))
+            };
             scene.rectangles.push(Rectangle::new(
                 COLORS[thread_id % COLORS.len()],
                 (
@@ -367,17 +537,164 @@ fn compute_idle_times(
                     starting_position.1 + thread_id as f64 * (1.0 + VERTICAL_GAP),
                 ),
                 (inactivity, 1.0),
-                (previous_end, start),
-                once(("_NO_TAGS_".to_string(), ("idle".to_string(), 1.0))).collect(),
+                (start, end),
+                once((
+                    "_NO_TAGS_".to_string(),
+                    (label, opacity / inactivity.max(1.0)),
+                ))
+                .collect(),
             ));
             current_x_positions[thread_id] += inactivity;
+        };
+
+    for (thread_id, start, end, label, opacity) in idle_periods {
+        let weighted_opacity = opacity * ((end - start) as f64).max(1.0);
+        pending[thread_id] = match pending[thread_id].take() {
+            Some((s, _, idle, count, l, o)) if (idle as f64) < min_width => Some((
+                s,
+                end,
+                idle + end - start,
+                count + 1,
+                l,
+                o + weighted_opacity,
+            )),
+            previous => {
+                if let Some(period) = previous {
+                    push_idle(thread_id, period);
+                }
+                Some((start, end, end - start, 1, label, weighted_opacity))
+            }
+        };
+    }
+    for (thread_id, period) in pending.into_iter().enumerate() {
+        if let Some(period) = period {
+            push_idle(thread_id, period);
+        }
+    }
+}
+
+/// Add the parallelism profile below the idle times, as a bar for each step.
+/// The total profile is shown for pseudo-tags and each tag shows the profile of its subgraphs.
+/// Consecutive steps are merged (averaging the number of active threads) until they are
+/// at least `min_width` wide.
+fn compute_parallelism_profile(
+    log: &RunLog,
+    starting_position: &(f64, f64),
+    scene: &mut Scene,
+    min_width: f64,
+) {
+    let threads_number = log.threads_number as f64;
+    let first_time = log
+        .tasks_logs
+        .iter()
+        .map(|t| t.start_time)
+        .min()
+        .unwrap_or(0);
+    let y = starting_position.1 + threads_number * (1.0 + VERTICAL_GAP) + 1.0;
+    let total_profile = log.parallelism_profile();
+    let tags_profiles = log.tags_parallelism_profiles();
+    scene.time_axis = Some((
+        (starting_position.0, y + threads_number),
+        first_time,
+        total_profile
+            .last()
+            .map(|&(time, _)| time)
+            .unwrap_or(first_time),
+    ));
+    let profiles = once((vec!["_NO_TAGS_", "_CRITICAL_PATH_"], &total_profile)).chain(
+        tags_profiles
+            .iter()
+            .map(|(tag, profile)| (vec![tag.as_str()], profile)),
+    );
+    for (tags, profile) in profiles {
+        // merged steps: start, end, number of steps and integral of active threads
+        let mut pending: Option<(u64, u64, usize, f64)> = None;
+        let mut push_bar = |(start, end, count, integral): (u64, u64, usize, f64)| {
+            let active = integral / (end - start) as f64;
+            if active == 0.0 {
+                return;
+            }
+            let label = if count == 1 {
+                format!("active threads: {}", active)
+            } else {
+                format!("average active threads: {:.2}", active)
+            };
+            scene.rectangles.push(Rectangle::new(
+                [0.5, 0.5, 0.5],
+                (
+                    starting_position.0 + (start - first_time) as f64,
+                    y + threads_number - active,
+                ),
+                ((end - start) as f64, active),
+                (start, end),
+                tags.iter()
+                    .map(|tag| (tag.to_string(), (label.clone(), 1.0)))
+                    .collect(),
+            ));
+        };
+        for step in profile.windows(2) {
+            let ((start, active), (end, _)) = (step[0], step[1]);
+            let integral = active as f64 * (end - start) as f64;
+            pending = match pending.take() {
+                Some((s, e, count, i)) if ((e - s) as f64) < min_width => {
+                    Some((s, end, count + 1, i + integral))
+                }
+                previous => {
+                    if let Some(bar) = previous {
+                        push_bar(bar);
+                    }
+                    Some((start, end, 1, integral))
+                }
+            };
+        }
+        if let Some(bar) = pending {
+            push_bar(bar);
         }
-        previous_activities[thread_id] = end;
     }
 }
 
+/// When to collapse blocks of the fork-join graph into summary rectangles.
+/// Summaries display their number of tasks, total work and threads involved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    /// Collapse blocks whose tasks durations sum up below given time (in ns).
+    Duration(TimeStamp),
+    /// Collapse blocks narrower than given number of pixels in a 1920 pixels wide display.
+    Pixels(f64),
+}
+
+/// Width of displays for `Aggregation::Pixels`.
+const DISPLAY_WIDTH: f64 = 1920.0;
+
 /// Computes a graphical view of a log. This is intended for the development of logs viewers.
 pub fn visualisation(log: &RunLog) -> Scene {
+    aggregated_visualisation(log, None)
+}
+
+/// Computes a graphical view of a log where small blocks of the fork-join graph
+/// are collapsed into summaries, according to given aggregation.
+/// Each summary rectangle keeps the detailed display in its `details`.
+/// This keeps huge logs displayable.
+///
+/// Example:
+///
+/// ```
+/// use rayon_logs::prelude::*;
+/// use rayon_logs::{aggregated_visualisation, visualisation, Aggregation, ThreadPoolBuilder};
+///
+/// let v: Vec<u64> = (0..100_000).collect();
+/// let pool = ThreadPoolBuilder::new()
+///     .num_threads(2)
+///     .build()
+///     .expect("building pool failed");
+/// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+/// let detailed = visualisation(&log);
+/// let aggregated = aggregated_visualisation(&log, Some(Aggregation::Duration(log.duration)));
+/// assert!(aggregated.rectangles.len() < detailed.rectangles.len());
+/// let summaries = aggregated.rectangles.iter().filter(|r| r.details.is_some()).count();
+/// assert!(summaries > 0);
+/// ```
+pub fn aggregated_visualisation(log: &RunLog, aggregation: Option<Aggregation>) -> Scene {
     let mut scene = Scene::new(log);
 
     let tasks = &log.tasks_logs;
@@ -406,6 +723,10 @@ pub fn visualisation(log: &RunLog) -> Scene {
         previous_h + blocks_dimensions[*root].1 + 1.0
     });
 
+    let collapsed = aggregation
+        .map(|a| collapsed_blocks(&root_blocks, &g, a, &blocks_dimensions, width))
+        .unwrap_or_default();
+
     let mut tasks_information = log.compute_tasks_information();
     for root in &root_blocks {
         generate_visualisation(
@@ -416,12 +737,66 @@ pub fn visualisation(log: &RunLog) -> Scene {
             &mut tasks_information,
             &log.tags,
             &blocks_dimensions,
+            &collapsed,
         );
     }
 
     let starting_position = (width as f64 * 0.1, height + 1.0);
 
-    compute_idle_times(tasks, &starting_position, log.threads_number, &mut scene);
+    // bars below the graph are merged when too small
+    let min_width = match aggregation {
+        None => 0.0,
+        Some(Aggregation::Duration(duration)) => duration as f64,
+        Some(Aggregation::Pixels(pixels)) => pixels * width / DISPLAY_WIDTH,
+    };
+    compute_idle_times(log, &starting_position, &mut scene, min_width);
+    compute_parallelism_profile(log, &starting_position, &mut scene, min_width);
 
     scene
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::IdlePeriod;
+
+    #[test]
+    fn merged_idle_periods_only_count_idle_time() {
+        let idle = |start, end| IdlePeriod {
+            thread_id: 0,
+            start,
+            end,
+            kind: IdleKind::Searching,
+        };
+        let mut log = RunLog::from_tasks(1, &[]);
+        log.duration = 1_000;
+        log.idle_periods = vec![idle(0, 10), idle(100, 120), idle(500, 1_000)];
+        let mut scene = Scene::new(&log);
+        compute_idle_times(&log, &(0.0, 0.0), &mut scene, 50.0);
+        let rectangles: Vec<_> = scene
+            .rectangles
+            .iter()
+            .map(|r| {
+                (
+                    r.x,
+                    r.width,
+                    r.animation,
+                    r.information["_NO_TAGS_"].0.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rectangles,
+            vec![(
+                0.0,
+                530.0,
+                (0, 1_000),
+                "3 idle periods (530ns idle)".to_string()
+            )]
+        );
+        let mut scene = Scene::new(&log);
+        compute_idle_times(&log, &(0.0, 0.0), &mut scene, 5.0);
+        let widths: Vec<f64> = scene.rectangles.iter().map(|r| r.width).collect();
+        assert_eq!(widths, vec![10.0, 20.0, 500.0]);
+    }
+}
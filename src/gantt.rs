@@ -0,0 +1,66 @@
+//! Gantt view of a log: one lane per thread and tasks at their real times.
+use crate::fork_join_graph::{displayed_idle_periods, VERTICAL_GAP};
+use crate::log::RunLog;
+use crate::svg::{Rectangle, Scene, COLORS};
+use std::iter::once;
+
+/// Vertical position of given thread's lane.
+fn lane(thread_id: usize) -> f64 {
+    thread_id as f64 * (1.0 + VERTICAL_GAP)
+}
+
+/// Computes a Gantt view of a log, using the same scene as `visualisation`.
+/// Each thread gets a lane where its tasks and idle periods are placed on a shared time axis.
+/// Dependencies between tasks running on different threads are drawn as arrows.
+pub fn gantt(log: &RunLog) -> Scene {
+    let mut scene = Scene::new(log);
+    let tasks = &log.tasks_logs;
+    let idle_periods = displayed_idle_periods(log);
+    let times = tasks
+        .iter()
+        .map(|t| (t.start_time, t.end_time))
+        .chain(idle_periods.iter().map(|p| (p.1, p.2)));
+    let (first_time, last_time) = times.fold((u64::MAX, 0), |(first, last), (s, e)| {
+        (first.min(s), last.max(e))
+    });
+    let first_time = first_time.min(last_time);
+    let x = |time: u64| (time - first_time) as f64;
+
+    let mut tasks_information = log.compute_tasks_information();
+    for (task_id, task) in tasks.iter().enumerate() {
+        let mut rectangle = Rectangle::new(
+            COLORS[task.thread_id % COLORS.len()],
+            (x(task.start_time), lane(task.thread_id)),
+            (task.duration() as f64, 1.0),
+            (task.start_time, task.end_time),
+            tasks_information.remove(&task_id).unwrap(),
+        );
+        rectangle.task = Some(task_id);
+        scene.rectangles.push(rectangle);
+    }
+
+    // idle periods are displayed in a thin bar at the bottom of each lane
+    for (thread_id, start, end, label, opacity) in idle_periods {
+        scene.rectangles.push(Rectangle::new(
+            COLORS[thread_id % COLORS.len()],
+            (x(start), lane(thread_id) + 0.8),
+            ((end - start) as f64, 0.2),
+            (start, end),
+            once(("_NO_TAGS_".to_string(), (label.to_string(), opacity))).collect(),
+        ));
+    }
+
+    for task in tasks {
+        for child in task.children.iter().map(|&c| &tasks[c]) {
+            if child.thread_id != task.thread_id {
+                scene.arrows.push((
+                    (x(task.end_time), lane(task.thread_id) + 0.5),
+                    (x(child.start_time), lane(child.thread_id) + 0.5),
+                ));
+            }
+        }
+    }
+
+    scene.time_axis = Some(((0.0, lane(log.threads_number)), first_time, last_time));
+    scene
+}
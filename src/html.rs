@@ -0,0 +1,136 @@
+//! Standalone html viewer.
+//! All data and code are embedded in the generated file so it works offline.
+//! Large traces stay readable since we can zoom and pan.
+use crate::fork_join_graph::{aggregated_visualisation, Aggregation};
+use crate::gantt::gantt;
+use crate::log::RunLog;
+use crate::svg::{Rectangle, Scene};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::iter::once;
+
+/// The viewer's code, data is inserted at the placeholder.
+const VIEWER: &str = include_str!("viewer.html");
+const PLACEHOLDER: &str = "/*DATA*/";
+
+/// Convert given scene to json.
+/// Tasks information is stored with the tasks so we only keep it for other rectangles.
+fn scene_json(name: &str, scene: &Scene) -> Value {
+    let bounds = scene.rectangles.iter().fold(
+        [f64::INFINITY, f64::INFINITY, 0.0, 0.0],
+        |[xmin, ymin, xmax, ymax], r| {
+            [
+                xmin.min(r.x),
+                ymin.min(r.y),
+                xmax.max(r.x + r.width),
+                ymax.max(r.y + r.height),
+            ]
+        },
+    );
+    json!({
+        "name": name,
+        "bounds": bounds,
+        "rectangles": rectangles_json(&scene.rectangles),
+        "segments": scene.segments,
+        "arrows": scene.arrows,
+        "axis": scene.time_axis,
+    })
+}
+
+/// Convert given rectangles to json, with the details of summaries.
+fn rectangles_json(rectangles: &[Rectangle]) -> Vec<Value> {
+    rectangles
+        .iter()
+        .map(|r| {
+            let color: Vec<u32> = r.color.iter().map(|c| (c * 255.0) as u32).collect();
+            let mut rectangle = json!({
+                "x": r.x,
+                "y": r.y,
+                "w": r.width,
+                "h": r.height,
+                "c": color,
+                "a": [r.animation.0, r.animation.1],
+                "t": r.task,
+                "i": if r.task.is_some() { json!({}) } else { json!(r.information) },
+            });
+            if let Some((details, segments)) = &r.details {
+                rectangle["d"] = json!(rectangles_json(details));
+                rectangle["s"] = json!(segments);
+            }
+            rectangle
+        })
+        .collect()
+}
+
+/// Iterate on given rectangles and on all rectangles in their details.
+fn all_rectangles<'a>(rectangles: &'a [Rectangle]) -> Box<dyn Iterator<Item = &'a Rectangle> + 'a> {
+    Box::new(rectangles.iter().flat_map(|r| {
+        once(r).chain(
+            r.details
+                .iter()
+                .flat_map(|(details, _)| all_rectangles(details)),
+        )
+    }))
+}
+
+/// Write the html viewer for given log.
+/// We display both the fork-join graph and the gantt chart.
+/// Blocks of the graph narrower than a pixel are collapsed, they expand when clicked.
+pub(crate) fn write_html<W: Write>(log: &RunLog, writer: &mut W) -> io::Result<()> {
+    let scenes = [
+        (
+            "fork-join graph",
+            aggregated_visualisation(log, Some(Aggregation::Pixels(1.0))),
+        ),
+        ("gantt", gantt(log)),
+    ];
+    // tasks labels for each tag
+    let tasks_information: HashMap<_, _> = all_rectangles(&scenes[0].1.rectangles)
+        .filter_map(|r| r.task.map(|t| (t, &r.information)))
+        .collect();
+    let mut parents = vec![Vec::new(); log.tasks_logs.len()];
+    for (task_id, task) in log.tasks_logs.iter().enumerate() {
+        for &child in &task.children {
+            parents[child].push(task_id);
+        }
+    }
+    let tasks: Vec<Value> = log
+        .tasks_logs
+        .iter()
+        .enumerate()
+        .map(|(task_id, task)| {
+            json!({
+                "thread": task.thread_id,
+                "start": task.start_time,
+                "end": task.end_time,
+                "children": task.children,
+                "parents": parents[task_id],
+                "i": tasks_information.get(&task_id),
+            })
+        })
+        .collect();
+    let start = log
+        .tasks_logs
+        .iter()
+        .map(|t| t.start_time)
+        .min()
+        .unwrap_or(0);
+    let end = log.tasks_logs.iter().map(|t| t.end_time).max().unwrap_or(0);
+    let data = json!({
+        "tags": scenes[0].1.tags,
+        "tasks": tasks,
+        "views": scenes
+            .iter()
+            .map(|(name, scene)| scene_json(name, scene))
+            .collect::<Vec<_>>(),
+        "start": start,
+        "end": end,
+    });
+    // closing tags inside strings would end the script
+    let data = data.to_string().replace("</", "<\\/");
+    let (head, tail) = VIEWER.split_at(VIEWER.find(PLACEHOLDER).unwrap());
+    writer.write_all(head.as_bytes())?;
+    writer.write_all(data.as_bytes())?;
+    writer.write_all(&tail.as_bytes()[PLACEHOLDER.len()..])
+}
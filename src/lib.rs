@@ -37,6 +37,14 @@
 //! Running this code will create a `log_0.json` file.
 //! You can then use `cargo run --bin json2svg -- log_0.json example_sum.svg` to view the log.
 //! The resulting file should be viewed in a web browser since it is animated.
+//! For large logs, give an `.html` file name instead: you will get an interactive viewer
+//! with zoom, pan, search and details on each task.
+//! Add `--static` for a non animated file (with a time axis and threads colors) which can be
+//! included in papers or opened by any image viewer.
+//! Add `--gantt` to display one lane per thread with tasks at their real times instead of the
+//! fork-join graph.
+//! Huge svg files can be avoided with `--min-pixels=<pixels>` or `--min-duration=<ns>`:
+//! small blocks of the graph are then displayed as one summary rectangle.
 //! The bars below the graph represent idle times.
 //!
 //! <div>
@@ -64,13 +72,34 @@ mod scope;
 pub use crate::scope::{scope, scope_fifo, Scope, ScopeFifo};
 mod fork_join_graph;
 mod stats;
-pub use crate::fork_join_graph::visualisation;
+pub use crate::fork_join_graph::{aggregated_visualisation, visualisation, Aggregation};
+mod gantt;
+pub use crate::gantt::gantt;
+mod html;
 pub(crate) mod compare;
 mod log;
-pub use crate::log::RunLog;
+pub use crate::log::{IdleKind, IdlePeriod, LogFormat, RunLog, StealLog, TaskLog, WorkSpan};
 mod rayon_algorithms;
 pub(crate) mod svg;
+pub use crate::svg::SvgOptions;
 pub use crate::compare::Comparator;
+mod binary;
+mod error;
+pub use crate::error::Error;
+mod validation;
+pub use crate::validation::Violation;
+mod simulator;
+pub use crate::simulator::SchedulingPolicy;
+mod profile;
+pub use crate::profile::Profile;
+mod chrome_trace;
+mod flamegraph;
+mod dot;
+mod summary;
+mod baseline;
+pub use crate::baseline::{BaselineVerdict, LabelVerdict, RegressionStatus};
+mod metadata;
+pub use crate::metadata::{Metadata, SCHEMA_VERSION};
 pub(crate) mod raw_events;
 /// We re-export rayon's `current_num_threads`.
 pub use rayon::current_num_threads;
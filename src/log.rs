@@ -1,20 +1,33 @@
 //! Provide structures holding all logged information for all tasks.
 //! This structure provides intermediate level information.
 //! It is a dag of tasks stored in a vector (using indices as pointers).
-use crate::fork_join_graph::visualisation;
+use crate::binary;
+use crate::chrome_trace::write_chrome_trace;
+use crate::dot::write_dot;
+use crate::error::Error;
+use crate::flamegraph::{folded_stacks, write_folded_stacks, write_speedscope};
+use crate::fork_join_graph::aggregated_visualisation;
+use crate::gantt::gantt;
+use crate::html::write_html;
+use crate::metadata::{migrate, Metadata};
+use crate::profile::{parallelism_profile, write_profiles_csv, Profile};
 use crate::raw_events::{RayonEvent, TaskId, TimeStamp};
-use crate::storage::Storage;
-use crate::svg::write_svg_file;
+use crate::simulator::{simulate, SchedulingPolicy};
+use crate::storage::{read_records, Storage};
+use crate::summary::write_summary;
+use crate::svg::{write_svg_file, SvgOptions};
+use crate::validation::{violations, Violation};
 use itertools::Itertools;
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
+use serde_json::{self, Value};
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
+use std::fmt;
+use std::fs::{read_dir, File};
 use std::io;
-use std::io::ErrorKind;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
 use std::iter::successors;
-use std::iter::{repeat, repeat_with};
+use std::iter::{once, repeat, repeat_with};
 use std::path::Path;
 use std::sync::Arc;
 
@@ -26,7 +39,7 @@ use std::sync::Arc;
 /// - the 2 join tasks
 /// - the sequential code executed after the join.
 /// The set of all tasks form a fork join graph.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TaskLog {
     /// starting time (in ns after pool creation)
     pub start_time: TimeStamp,
@@ -45,11 +58,154 @@ impl TaskLog {
     }
 }
 
+/// A steal (or a steal attempt) by a thread.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StealLog {
+    /// when did it happen (in ns after pool creation)
+    pub time: TimeStamp,
+    /// id of thread who tried stealing
+    pub thief: usize,
+    /// id of thread we stole from (if known)
+    pub victim: Option<usize>,
+    /// stolen task (none for failed steals)
+    pub task: Option<TaskId>,
+    /// number of steal attempts this entry stands for.
+    /// failed steals are aggregated: one entry counts all failed attempts of its thief since
+    /// the previous entry (see `RunLog::failed_steals`).
+    #[serde(default = "one")]
+    pub attempts: usize,
+}
+
+/// Default number of attempts of a steal.
+fn one() -> usize {
+    1
+}
+
+impl StealLog {
+    /// Did we get anything.
+    pub fn successful(&self) -> bool {
+        self.task.is_some()
+    }
+}
+
+/// What an idle thread is doing.
+///
+/// Threads record when they enter or leave rayon's steal loop and when they fall asleep
+/// or wake up (see `ThreadPoolBuilder::build`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum IdleKind {
+    /// Looking for some work to steal.
+    Searching,
+    /// Asleep, waiting for rayon to wake it up.
+    Sleeping,
+}
+
+impl IdleKind {
+    /// Return a label for displays.
+    pub fn label(self) -> &'static str {
+        match self {
+            IdleKind::Searching => "searching",
+            IdleKind::Sleeping => "sleeping",
+        }
+    }
+}
+
+/// A period of time where a thread runs no task.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IdlePeriod {
+    /// idle thread
+    pub thread_id: usize,
+    /// starting time (in ns after pool creation)
+    pub start: TimeStamp,
+    /// ending time (in ns after pool creation)
+    pub end: TimeStamp,
+    /// what the thread was doing
+    pub kind: IdleKind,
+}
+
+impl IdlePeriod {
+    /// Return the period of given kind ending at given time, if not empty.
+    /// Periods whose start we missed start with the log.
+    fn ending(
+        thread_id: usize,
+        start: Option<TimeStamp>,
+        end: TimeStamp,
+        kind: IdleKind,
+    ) -> Option<IdlePeriod> {
+        let start = start.unwrap_or(0);
+        if start < end {
+            Some(IdlePeriod {
+                thread_id,
+                start,
+                end,
+                kind,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Classical work-span analysis of a run (or part of a run).
+/// All times are in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkSpan {
+    /// total work T1 (sum of all tasks durations).
+    pub work: TimeStamp,
+    /// span T∞ (critical path length).
+    pub span: TimeStamp,
+    /// time elapsed between first start and last end.
+    pub duration: TimeStamp,
+    /// number of threads available.
+    pub threads_number: usize,
+}
+
+impl WorkSpan {
+    /// Available parallelism T1/T∞ (the best speedup we could hope for).
+    pub fn parallelism(&self) -> f64 {
+        self.work as f64 / self.span as f64
+    }
+
+    /// Achieved speedup T1/Tp.
+    pub fn speedup(&self) -> f64 {
+        self.work as f64 / self.duration as f64
+    }
+
+    /// Achieved speedup divided by the number of threads.
+    pub fn efficiency(&self) -> f64 {
+        self.speedup() / self.threads_number as f64
+    }
+
+    /// Brent's bound T1/p + T∞ on the execution time of a greedy scheduler on p threads.
+    pub fn brent_bound(&self, threads_number: usize) -> TimeStamp {
+        self.work / threads_number as TimeStamp + self.span
+    }
+}
+
+/// Supported log files formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable (but huge) json.
+    Json,
+    /// Compact binary format.
+    Binary,
+}
+
+impl LogFormat {
+    /// Figure out format from file extension: ".rlog" files are binary, all others are json.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("rlog") => LogFormat::Binary,
+            _ => LogFormat::Json,
+        }
+    }
+}
+
 /// Logged information.
 ///
 /// This stores tasks information, threads number and run duration.
 /// Obtained by `ThreadPool::install`.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct RunLog {
     /// total number of threads (some might be unused).
     pub threads_number: usize,
@@ -62,16 +218,218 @@ pub struct RunLog {
     /// subgraphs: some parts of the graph can be tagged with a tag and usize
     /// values are: start task, ending task, tag_id, recorded size
     pub subgraphs: Vec<(TaskId, TaskId, usize, usize)>,
+    /// for each subgraph, the index of the innermost subgraph containing it (if any).
+    /// it is recorded when the subgraph starts.
+    /// following parents gives the stack of nested tags.
+    #[serde(default)]
+    pub subgraphs_parents: Vec<Option<usize>>,
+    /// all steals (successful or not), sorted by time.
+    #[serde(default)]
+    pub steals: Vec<StealLog>,
+    /// all recorded idle periods, sorted by starting time.
+    /// time spent between tasks outside of rayon's steal loop is not part of any period.
+    #[serde(default)]
+    pub idle_periods: Vec<IdlePeriod>,
+    /// true if we only have the end of the history (see `ThreadPoolBuilder::flight_recorder`).
+    /// in this case tasks whose start was overwritten are missing, links to them are lost
+    /// and tasks are re-numbered.
+    #[serde(default)]
+    pub partial: bool,
+    /// where and how the log was produced.
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl fmt::Display for RunLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_summary(self, 80, false, f)
+    }
 }
 
 impl RunLog {
     /// Create a real log from logged events and reset the pool.
     pub(crate) fn new(
         tasks_number: usize,
-        _iterators_number: usize,
+        iterators_number: usize,
+        tasks_logs: &[Arc<Storage<RayonEvent>>],
+        start: TimeStamp,
+    ) -> Result<Self, Error> {
+        RunLog::from_events(
+            tasks_number,
+            iterators_number,
+            tasks_logs.iter().map(|l| l.iter()).collect(),
+            start,
+        )
+    }
+
+    /// Create a real log from logged events streamed to disk (one file per thread).
+    pub(crate) fn from_streams<P: AsRef<Path>>(
+        tasks_number: usize,
+        iterators_number: usize,
+        paths: &[P],
+        start: TimeStamp,
+    ) -> Result<Self, Error> {
+        let threads_events = paths
+            .iter()
+            .map(read_records)
+            .collect::<Result<Vec<Vec<RayonEvent>>, _>>()?;
+        RunLog::from_events(
+            tasks_number,
+            iterators_number,
+            threads_events.iter().map(|events| events.iter()).collect(),
+            start,
+        )
+    }
+
+    /// Rebuild a log from all raw events files streamed to given directory
+    /// (see `ThreadPoolBuilder::stream_logs`).
+    pub fn load_streamed<P: AsRef<Path>>(directory: P) -> Result<RunLog, Error> {
+        let mut paths = Vec::new();
+        for entry in read_dir(directory)? {
+            let path = entry?.path();
+            let thread_index = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix("thread_"))
+                .and_then(|n| n.strip_suffix(".bin"))
+                .and_then(|n| n.parse::<usize>().ok());
+            if let Some(index) = thread_index {
+                paths.push((index, path));
+            }
+        }
+        paths.sort();
+        let threads_events = paths
+            .iter()
+            .map(|(_, path)| read_records(path))
+            .collect::<Result<Vec<Vec<RayonEvent>>, _>>()?;
+        // tasks ids are contiguous and time starts with the first event
+        let tasks_number = threads_events
+            .iter()
+            .flatten()
+            .filter_map(|e| match *e {
+                RayonEvent::TaskStart(task, _) => Some(task + 1),
+                _ => None,
+            })
+            .max()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "no tasks in logs"))?;
+        let start = threads_events
+            .iter()
+            .flatten()
+            .filter(|e| {
+                !matches!(
+                    e,
+                    RayonEvent::Child(_)
+                        | RayonEvent::SubgraphStart(_)
+                        | RayonEvent::SubgraphEnd(_, _)
+                )
+            })
+            .map(|e| e.time())
+            .min()
+            .unwrap();
+        RunLog::from_events(
+            tasks_number,
+            0,
+            threads_events.iter().map(|events| events.iter()).collect(),
+            start,
+        )
+    }
+
+    /// Create a log from the (possibly truncated) histories of a flight recorder.
+    pub(crate) fn from_partial_events(
         tasks_logs: &[Arc<Storage<RayonEvent>>],
+    ) -> Result<Self, Error> {
+        let threads_events: Vec<Vec<RayonEvent>> = tasks_logs
+            .iter()
+            .map(|l| complete_events(l.iter()))
+            .collect();
+        // tasks ids keep on growing so we re-number all tasks we still have
+        let new_ids: HashMap<TaskId, TaskId> = threads_events
+            .iter()
+            .flatten()
+            .filter_map(|e| match *e {
+                RayonEvent::TaskStart(task, _) => Some(task),
+                _ => None,
+            })
+            .sorted()
+            .enumerate()
+            .map(|(new_id, task)| (task, new_id))
+            .collect();
+        let start = threads_events
+            .iter()
+            .flatten()
+            .filter_map(|e| match *e {
+                RayonEvent::TaskStart(_, time) => Some(time),
+                _ => None,
+            })
+            .min();
+        let start = match start {
+            Some(time) => time,
+            None => {
+                return Ok(RunLog {
+                    threads_number: tasks_logs.len(),
+                    tasks_logs: Vec::new(),
+                    duration: 0,
+                    tags: Vec::new(),
+                    subgraphs: Vec::new(),
+                    subgraphs_parents: Vec::new(),
+                    steals: Vec::new(),
+                    idle_periods: Vec::new(),
+                    partial: true,
+                    metadata: Metadata::unknown(),
+                })
+            }
+        };
+        let renumbered_events: Vec<Vec<RayonEvent>> = threads_events
+            .into_iter()
+            .map(|events| {
+                events
+                    .into_iter()
+                    .filter_map(|e| match e {
+                        RayonEvent::TaskStart(task, time) => {
+                            Some(RayonEvent::TaskStart(new_ids[&task], time))
+                        }
+                        RayonEvent::Child(task) => {
+                            new_ids.get(&task).map(|t| RayonEvent::Child(*t))
+                        }
+                        RayonEvent::Steal(task, time) => {
+                            new_ids.get(&task).map(|t| RayonEvent::Steal(*t, time))
+                        }
+                        // other threads histories might start later than this one's
+                        RayonEvent::FailedSteals(time, _)
+                        | RayonEvent::SearchStart(time)
+                        | RayonEvent::SearchEnd(time)
+                        | RayonEvent::SleepStart(time)
+                        | RayonEvent::SleepEnd(time)
+                            if time < start =>
+                        {
+                            None
+                        }
+                        e => Some(e),
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut log = RunLog::from_events(
+            new_ids.len(),
+            0,
+            renumbered_events
+                .iter()
+                .map(|events| events.iter())
+                .collect(),
+            start,
+        )?;
+        log.partial = tasks_logs.iter().any(|l| l.is_truncated());
+        Ok(log)
+    }
+
+    /// Create a real log from the sequence of logged events of each thread.
+    /// Fails if events do not form a valid tasks graph.
+    fn from_events<'e, I: Iterator<Item = &'e RayonEvent>>(
+        tasks_number: usize,
+        _iterators_number: usize,
+        threads_events: Vec<I>,
         start: TimeStamp,
-    ) -> Self {
+    ) -> Result<Self, Error> {
         let mut seen_tags = HashMap::new(); // associate each take to a usize index
         let mut tags = Vec::new(); // vector containing all tags strings
         let mut tasks_info: Vec<_> = (0..tasks_number)
@@ -83,7 +441,7 @@ impl RunLog {
             })
             .collect();
 
-        let threads_number = tasks_logs.len();
+        let threads_number = threads_events.len();
         // remember the active task on each thread
         let mut all_active_tasks: Vec<Option<TaskId>> = repeat(None).take(threads_number).collect();
         // remember the active subgraph on each thread (they for a stack)
@@ -92,31 +450,114 @@ impl RunLog {
 
         // store all subgraph related informations
         let mut subgraphs = Vec::new();
+        let mut subgraphs_parents = Vec::new();
+        // innermost subgraph in which each task got created and current one on each thread
+        let mut tasks_subgraphs: Vec<Option<usize>> = vec![None; tasks_number];
+        let mut current_subgraphs: Vec<Option<usize>> = vec![None; threads_number];
 
-        for (thread_id, event) in tasks_logs
-            .iter()
+        // store all steals. we will figure out victims of successful steals at the end
+        let mut steals = Vec::new();
+        let mut stolen_tasks = HashSet::new();
+
+        // when each thread started searching for work or sleeping (if it still does)
+        let mut idle_periods = Vec::new();
+        let mut searching_since: Vec<Option<TimeStamp>> = vec![None; threads_number];
+        let mut sleeping_since: Vec<Option<TimeStamp>> = vec![None; threads_number];
+
+        for (thread_id, event) in threads_events
+            .into_iter()
             .enumerate()
-            .map(|(thread_id, thread_log)| thread_log.iter().map(move |log| (thread_id, log)))
+            .map(|(thread_id, thread_log)| thread_log.map(move |log| (thread_id, log)))
             .kmerge_by(|a, b| a.1.time() < b.1.time())
         {
             let active_tasks = &mut all_active_tasks[thread_id];
             let active_subgraphs = &mut all_active_subgraphs[thread_id];
+            let elapsed = |time: TimeStamp| {
+                time.checked_sub(start).ok_or_else(|| {
+                    Error::inconsistent(None, thread_id, "event happening before the log starts")
+                })
+            };
             match *event {
                 RayonEvent::Child(c) => {
-                    let father = active_tasks.expect("child with no active task as father");
+                    let father = active_tasks.ok_or_else(|| {
+                        Error::inconsistent(
+                            Some(c),
+                            thread_id,
+                            "child with no active task as father",
+                        )
+                    })?;
+                    if c >= tasks_number {
+                        return Err(Error::inconsistent(
+                            Some(c),
+                            thread_id,
+                            "unknown child task",
+                        ));
+                    }
                     tasks_info[father].children.push(c);
+                    tasks_subgraphs[c] = current_subgraphs[thread_id];
                 }
                 RayonEvent::TaskEnd(time) => {
                     if let Some(task) = active_tasks.take() {
-                        tasks_info[task].end_time = time - start;
+                        tasks_info[task].end_time = elapsed(time)?;
                     } else {
-                        panic!("ending a non started task. are you mixing logged and un-logged computations ?");
+                        return Err(Error::inconsistent(
+                            None,
+                            thread_id,
+                            "ending a non started task. are you mixing logged and un-logged computations ?",
+                        ));
                     }
                 }
                 RayonEvent::TaskStart(task, time) => {
-                    tasks_info[task].thread_id = thread_id;
-                    tasks_info[task].start_time = time - start;
+                    let task_info = tasks_info.get_mut(task).ok_or_else(|| {
+                        Error::inconsistent(Some(task), thread_id, "starting an unknown task")
+                    })?;
+                    task_info.thread_id = thread_id;
+                    task_info.start_time = elapsed(time)?;
                     *active_tasks = Some(task);
+                    current_subgraphs[thread_id] = tasks_subgraphs[task];
+                }
+                RayonEvent::Steal(task, time) => {
+                    stolen_tasks.insert(task);
+                    steals.push(StealLog {
+                        time: elapsed(time)?,
+                        thief: thread_id,
+                        victim: None,
+                        task: Some(task),
+                        attempts: 1,
+                    });
+                }
+                RayonEvent::FailedSteals(time, attempts) => steals.push(StealLog {
+                    time: elapsed(time)?,
+                    thief: thread_id,
+                    victim: None,
+                    task: None,
+                    attempts,
+                }),
+                RayonEvent::SearchStart(time) => searching_since[thread_id] = Some(elapsed(time)?),
+                RayonEvent::SearchEnd(time) => idle_periods.extend(IdlePeriod::ending(
+                    thread_id,
+                    searching_since[thread_id].take(),
+                    elapsed(time)?,
+                    IdleKind::Searching,
+                )),
+                RayonEvent::SleepStart(time) => {
+                    // threads fall asleep inside the steal loop
+                    idle_periods.extend(IdlePeriod::ending(
+                        thread_id,
+                        searching_since[thread_id].take(),
+                        elapsed(time)?,
+                        IdleKind::Searching,
+                    ));
+                    sleeping_since[thread_id] = Some(elapsed(time)?);
+                }
+                RayonEvent::SleepEnd(time) => {
+                    idle_periods.extend(IdlePeriod::ending(
+                        thread_id,
+                        sleeping_since[thread_id].take(),
+                        elapsed(time)?,
+                        IdleKind::Sleeping,
+                    ));
+                    searching_since[thread_id] = Some(elapsed(time)?);
                 }
                 RayonEvent::SubgraphStart(work_type) | RayonEvent::SubgraphEnd(work_type, _) => {
                     if let Some(active_task) = active_tasks {
@@ -132,40 +573,187 @@ impl RunLog {
                         };
                         match *event {
                             RayonEvent::SubgraphStart(_) => {
+                                subgraphs_parents.push(current_subgraphs[thread_id]);
+                                current_subgraphs[thread_id] = Some(subgraphs.len());
                                 active_subgraphs.push(subgraphs.len());
                                 subgraphs.push((*active_task, 0, tag_index, 0));
                             }
                             RayonEvent::SubgraphEnd(_, work_amount) => {
-                                let graph_index =
-                                    active_subgraphs.pop().expect("ending a non started graph");
+                                let graph_index = active_subgraphs.pop().ok_or_else(|| {
+                                    Error::inconsistent(
+                                        Some(*active_task),
+                                        thread_id,
+                                        "ending a non started graph",
+                                    )
+                                })?;
                                 subgraphs[graph_index].1 = *active_task;
                                 subgraphs[graph_index].3 = work_amount;
+                                current_subgraphs[thread_id] = subgraphs_parents[graph_index];
                             }
                             _ => unreachable!(),
                         }
                     } else {
-                        panic!("tagging a non existing task");
+                        return Err(Error::inconsistent(
+                            None,
+                            thread_id,
+                            "tagging a non existing task",
+                        ));
                     }
                 }
             }
         }
 
-        let duration = tasks_info.iter().map(|t| t.end_time).max().unwrap()
-            - tasks_info.iter().map(|t| t.start_time).min().unwrap();
+        // tasks still running when logs got extracted (flight recorder) have no end
+        for task in tasks_info.iter_mut().filter(|t| t.end_time < t.start_time) {
+            task.end_time = task.start_time;
+        }
+
+        let no_tasks = || Error::inconsistent(None, 0, "no tasks in logs");
+        let last_time = tasks_info
+            .iter()
+            .map(|t| t.end_time)
+            .max()
+            .ok_or_else(no_tasks)?;
+        let first_time = tasks_info
+            .iter()
+            .map(|t| t.start_time)
+            .min()
+            .ok_or_else(no_tasks)?;
+        let duration = last_time - first_time;
 
-        RunLog {
+        // stolen tasks are robbed from the thread running their father
+        let victims: HashMap<TaskId, usize> = tasks_info
+            .iter()
+            .flat_map(|t| t.children.iter().map(move |c| (*c, t.thread_id)))
+            .filter(|(c, _)| stolen_tasks.contains(c))
+            .collect();
+        for steal in &mut steals {
+            steal.victim = steal.task.and_then(|t| victims.get(&t).cloned());
+        }
+        // idle threads keep on trying to steal after the last task ended
+        steals.retain(|s| s.time <= last_time);
+
+        // periods still open are cut at the end, like the ones going on before the first task
+        // or after the last one
+        let still_idle = searching_since
+            .into_iter()
+            .map(|since| (since, IdleKind::Searching))
+            .enumerate()
+            .chain(
+                sleeping_since
+                    .into_iter()
+                    .map(|since| (since, IdleKind::Sleeping))
+                    .enumerate(),
+            );
+        for (thread_id, (since, kind)) in still_idle {
+            if since.is_some() {
+                idle_periods.extend(IdlePeriod::ending(thread_id, since, last_time, kind));
+            }
+        }
+        let mut idle_periods: Vec<IdlePeriod> = idle_periods
+            .into_iter()
+            .filter_map(|p| {
+                IdlePeriod::ending(
+                    p.thread_id,
+                    Some(p.start.max(first_time)),
+                    p.end.min(last_time),
+                    p.kind,
+                )
+            })
+            .collect();
+        idle_periods.sort_by_key(|p| (p.start, p.thread_id));
+
+        Ok(RunLog {
             threads_number,
             tasks_logs: tasks_info,
             duration,
             tags,
             subgraphs,
+            subgraphs_parents,
+            steals,
+            idle_periods,
+            partial: false,
+            metadata: Metadata::unknown(),
+        })
+    }
+
+    /// Return for each task the innermost subgraph containing it.
+    /// Subgraphs are ordered by starting time so outer subgraphs come before inner ones.
+    pub(crate) fn innermost_subgraphs(&self) -> Vec<Option<usize>> {
+        let mut innermost_subgraphs = vec![None; self.tasks_logs.len()];
+        for (index, &(start_task, end_task, _, _)) in self.subgraphs.iter().enumerate() {
+            for task in self.tasks_between(start_task, end_task) {
+                innermost_subgraphs[task] = Some(index);
+            }
+        }
+        innermost_subgraphs
+    }
+
+    /// Logs from before schema version 1 do not store subgraphs nesting, we re-compute it
+    /// from the tasks each subgraph contains.
+    fn fill_subgraphs_parents(mut self) -> Self {
+        if self.subgraphs_parents.len() != self.subgraphs.len() {
+            let mut innermost_subgraphs = vec![None; self.tasks_logs.len()];
+            let mut parents = Vec::with_capacity(self.subgraphs.len());
+            for (index, &(start_task, end_task, _, _)) in self.subgraphs.iter().enumerate() {
+                parents.push(innermost_subgraphs[start_task]);
+                for task in self.tasks_between(start_task, end_task) {
+                    innermost_subgraphs[task] = Some(index);
+                }
+            }
+            self.subgraphs_parents = parents;
         }
+        self
+    }
+
+    /// Return for each task the stack of tags (outermost first) of all subgraphs containing it.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::{join, subgraph, ThreadPoolBuilder};
+    ///
+    /// fn sum(slice: &[u64]) -> u64 {
+    ///     if slice.len() < 1_000 {
+    ///         subgraph("leaf", slice.len(), || slice.iter().sum())
+    ///     } else {
+    ///         let (left, right) = slice.split_at(slice.len() / 2);
+    ///         let (sleft, sright) = join(|| sum(left), || sum(right));
+    ///         sleft + sright
+    ///     }
+    /// }
+    ///
+    /// let v: Vec<u64> = (0..10_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| subgraph("sum", v.len(), || sum(&v)));
+    /// let stacks = log.tasks_tag_stacks();
+    /// assert!(stacks.iter().any(|s| s == &["sum", "leaf"]));
+    /// assert!(stacks.iter().all(|s| s.is_empty() || s[0] == "sum"));
+    /// let dir = std::env::temp_dir();
+    /// log.save_folded_stacks(dir.join("sum.folded")).expect("saving folded stacks failed");
+    /// log.save_speedscope(dir.join("sum.speedscope.json")).expect("saving speedscope failed");
+    /// ```
+    pub fn tasks_tag_stacks(&self) -> Vec<Vec<&str>> {
+        let parents = &self.subgraphs_parents;
+        self.innermost_subgraphs()
+            .into_iter()
+            .map(|innermost| {
+                let mut stack: Vec<&str> = successors(innermost, |&s| parents[s])
+                    .map(|s| self.tags[self.subgraphs[s].2].as_str())
+                    .collect();
+                stack.reverse();
+                stack
+            })
+            .collect()
     }
 
     /// Iterate on all tasks between (including) start and end.
     /// pre-condition: start is ancestor of end and all descendants of start
     /// are either ancestors of end or descendants of end.
-    fn tasks_between<'a>(
+    pub(crate) fn tasks_between<'a>(
         &'a self,
         start: TaskId,
         end: TaskId,
@@ -223,6 +811,7 @@ impl RunLog {
             }
         }
         // ok, we are now ready to compute tasks information
+        let stacks = self.tasks_tag_stacks();
         let mut tasks_information = HashMap::new();
         for (subgraph_index, (start_task, end_task, tag_id, size)) in
             self.subgraphs.iter().enumerate()
@@ -240,19 +829,41 @@ impl RunLog {
                         self.tags[*tag_id].clone(),
                         (
                             format!(
-                                "task: {}\ncounted: {}/{}\nduration: {} (micro sec)\nspeed: {}\nthread: {}",
+                                "task: {}\ncounted: {}/{}\nduration: {} (micro sec)\nspeed: {}\nthread: {}\nstack: {}",
                                 task,
                                 size_part,
                                 size,
                                 duration / 1000,
                                 speed,
-                                self.tasks_logs[task].thread_id
+                                self.tasks_logs[task].thread_id,
+                                stacks[task].join(";")
                             ),
                             0.4 + speed * 0.6,
                         ),
                     );
             }
         }
+        // highlight the critical path
+        let critical_tasks: HashSet<TaskId> = self.critical_path().0.into_iter().collect();
+        for (task_id, task) in self.tasks_logs.iter().enumerate() {
+            let critical = critical_tasks.contains(&task_id);
+            tasks_information
+                .entry(task_id)
+                .or_insert_with(HashMap::new)
+                .insert(
+                    "_CRITICAL_PATH_".to_string(),
+                    (
+                        format!(
+                            "task: {}\nduration: {} (micro sec)\nthread: {}\ncritical: {}",
+                            task_id,
+                            task.duration() / 1000,
+                            task.thread_id,
+                            critical
+                        ),
+                        if critical { 1.0 } else { 0.1 },
+                    ),
+                );
+        }
         // final step, add information for no tags
         for (task_id, task) in self.tasks_logs.iter().enumerate() {
             let duration = task.duration();
@@ -321,22 +932,890 @@ impl RunLog {
         hash
     }
 
+    /// Return a text summary of the log, `width` characters wide, for terminals.
+    /// Each thread gets a line showing its activity over time followed by its utilisation.
+    /// We then display work, critical path, steals, idle times and statistics for each tag.
+    /// `ascii` avoids unicode block characters.
+    /// The `Display` implementation is the 80 characters wide unicode summary.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let summary = log.summary(60, true);
+    /// assert!(summary.lines().filter(|l| l.contains('|')).all(|l| l.len() == 60));
+    /// assert!(summary.is_ascii());
+    /// println!("{}", log);
+    /// ```
+    pub fn summary(&self, width: usize, ascii: bool) -> String {
+        let mut summary = String::new();
+        write_summary(self, width, ascii, &mut summary).expect("writing to a string failed");
+        summary
+    }
+
+    /// Return the number of successful steals.
+    pub fn successful_steals(&self) -> usize {
+        self.steals.iter().filter(|s| s.successful()).count()
+    }
+
+    /// Return the number of failed steal attempts.
+    pub fn failed_steals(&self) -> usize {
+        self.steals
+            .iter()
+            .filter(|s| !s.successful())
+            .map(|s| s.attempts)
+            .sum()
+    }
+
+    /// Return the total time (summed over all threads) spent idle doing given activity.
+    pub fn idle_time(&self, kind: IdleKind) -> TimeStamp {
+        self.idle_periods
+            .iter()
+            .filter(|p| p.kind == kind)
+            .map(|p| p.end - p.start)
+            .sum()
+    }
+
+    /// Return the chain of tasks (following `children` links) with the largest summed duration,
+    /// together with this total duration.
+    /// This is the critical path: no scheduler could run the graph faster.
+    /// Note that only tasks durations are accounted for (not the time spent between tasks).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let (path, length) = log.critical_path();
+    /// assert_eq!(path.first(), Some(&0)); // we start at the root
+    /// assert!(path.windows(2).all(|w| log.tasks_logs[w[0]].children.contains(&w[1])));
+    /// assert!(length <= log.duration);
+    /// ```
+    pub fn critical_path(&self) -> (Vec<TaskId>, TimeStamp) {
+        self.longest_path(|_| true)
+    }
+
+    /// Return the longest path (and its length) in the graph restricted to tasks we keep.
+    fn longest_path<F: Fn(TaskId) -> bool>(&self, keep: F) -> (Vec<TaskId>, TimeStamp) {
+        let tasks = &self.tasks_logs;
+        let kept_tasks: Vec<TaskId> = (0..tasks.len()).filter(|&t| keep(t)).collect();
+        let valid_children = |task: TaskId| {
+            tasks[task]
+                .children
+                .iter()
+                .cloned()
+                .filter(|&c| c < tasks.len() && keep(c))
+        };
+        // topological sort (tasks on cycles are ignored)
+        let mut fathers_number = vec![0; tasks.len()];
+        for &task in &kept_tasks {
+            for child in valid_children(task) {
+                fathers_number[child] += 1;
+            }
+        }
+        let mut ready: Vec<TaskId> = kept_tasks
+            .iter()
+            .cloned()
+            .filter(|&t| fathers_number[t] == 0)
+            .collect();
+        // longest path ending on each task, and previous task on this path
+        let mut lengths: Vec<TimeStamp> = tasks.iter().map(|t| t.duration()).collect();
+        let mut previous: Vec<Option<TaskId>> = vec![None; tasks.len()];
+        while let Some(task) = ready.pop() {
+            for child in valid_children(task) {
+                let length = lengths[task] + tasks[child].duration();
+                if previous[child].is_none() || length > lengths[child] {
+                    lengths[child] = length;
+                    previous[child] = Some(task);
+                }
+                fathers_number[child] -= 1;
+                if fathers_number[child] == 0 {
+                    ready.push(child);
+                }
+            }
+        }
+        let last_task = match kept_tasks.into_iter().max_by_key(|&t| lengths[t]) {
+            Some(task) => task,
+            None => return (Vec::new(), 0),
+        };
+        let mut path: Vec<TaskId> = successors(Some(last_task), |&t| previous[t]).collect();
+        path.reverse();
+        (path, lengths[last_task])
+    }
+
+    /// Return the work-span analysis of the whole run.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let analysis = log.work_span();
+    /// assert_eq!(analysis.span, log.critical_path().1);
+    /// assert!(analysis.span <= analysis.work);
+    /// assert!(analysis.parallelism() >= 1.0);
+    /// assert!(analysis.speedup() <= 2.0);
+    /// ```
+    pub fn work_span(&self) -> WorkSpan {
+        WorkSpan {
+            work: self.tasks_logs.iter().map(|t| t.duration()).sum(),
+            span: self.critical_path().1,
+            duration: self.duration,
+            threads_number: self.threads_number,
+        }
+    }
+
+    /// Return the work-span analysis of each subgraph (in the same order as `subgraphs`).
+    pub fn subgraphs_work_span(&self) -> Vec<WorkSpan> {
+        self.subgraphs
+            .iter()
+            .map(|&(start_task, end_task, _, _)| {
+                let tasks: HashSet<TaskId> = self.tasks_between(start_task, end_task).collect();
+                let (first_start, last_end) = tasks
+                    .iter()
+                    .map(|&t| (self.tasks_logs[t].start_time, self.tasks_logs[t].end_time))
+                    .fold((TimeStamp::MAX, 0), |(s1, e1), (s2, e2)| {
+                        (s1.min(s2), e1.max(e2))
+                    });
+                WorkSpan {
+                    work: tasks.iter().map(|&t| self.tasks_logs[t].duration()).sum(),
+                    span: self.longest_path(|t| tasks.contains(&t)).1,
+                    duration: last_end.saturating_sub(first_start),
+                    threads_number: self.threads_number,
+                }
+            })
+            .collect()
+    }
+
+    /// Replay our tasks graph on given number of threads with given scheduling policy.
+    /// Tasks keep their durations and we return the simulated log.
+    /// This way you can predict what would happen on a larger machine.
+    /// Pre-condition: the log is valid (see `validate`).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::{SchedulingPolicy, ThreadPoolBuilder};
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let analysis = log.work_span();
+    /// let sequential = log.simulate(1, SchedulingPolicy::Greedy);
+    /// assert_eq!(sequential.duration, analysis.work);
+    /// let greedy = log.simulate(64, SchedulingPolicy::Greedy);
+    /// assert!(greedy.duration <= analysis.brent_bound(64));
+    /// let stealing = SchedulingPolicy::WorkStealing {
+    ///     seed: 0,
+    ///     steal_duration: 1_000,
+    /// };
+    /// let simulated = log.simulate(64, stealing);
+    /// assert!(simulated.validate().is_empty());
+    /// assert!(simulated.duration >= analysis.span);
+    /// let dir = std::env::temp_dir();
+    /// simulated.save_svg(dir.join("simulated_sum.svg")).expect("saving svg failed");
+    /// ```
+    pub fn simulate(&self, threads_number: usize, policy: SchedulingPolicy) -> RunLog {
+        simulate(self, threads_number, policy)
+    }
+
+    /// Return the parallelism profile of the run: a step function giving the number
+    /// of threads executing a task at each time.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let profile = log.parallelism_profile();
+    /// assert!(profile.iter().all(|&(_, active)| active <= 2));
+    /// assert_eq!(profile.last().map(|&(_, active)| active), Some(0));
+    /// // summing the profile gives back the total work
+    /// let work: u64 = profile
+    ///     .windows(2)
+    ///     .map(|w| (w[1].0 - w[0].0) * w[0].1 as u64)
+    ///     .sum();
+    /// assert_eq!(work, log.work_span().work);
+    /// let dir = std::env::temp_dir();
+    /// log.save_parallelism_profile(dir.join("sum_profile.csv")).expect("saving csv failed");
+    /// ```
+    pub fn parallelism_profile(&self) -> Profile {
+        parallelism_profile(self.tasks_logs.iter())
+    }
+
+    /// Return for each tag the parallelism profile of all tasks inside subgraphs with this tag.
+    pub fn tags_parallelism_profiles(&self) -> HashMap<String, Profile> {
+        let mut tags_tasks: HashMap<usize, HashSet<TaskId>> = HashMap::new();
+        for &(start_task, end_task, tag_id, _) in &self.subgraphs {
+            tags_tasks
+                .entry(tag_id)
+                .or_default()
+                .extend(self.tasks_between(start_task, end_task));
+        }
+        tags_tasks
+            .into_iter()
+            .map(|(tag_id, tasks)| {
+                (
+                    self.tags[tag_id].clone(),
+                    parallelism_profile(tasks.into_iter().map(|t| &self.tasks_logs[t])),
+                )
+            })
+            .collect()
+    }
+
+    /// Save the parallelism profile as a csv file.
+    /// First column is the time, second one the number of active threads
+    /// and we then have one column for each tag.
+    pub fn save_parallelism_profile<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let total_profile = self.parallelism_profile();
+        let tags_profiles = self.tags_parallelism_profiles();
+        let profiles: Vec<(&str, &Profile)> = once(("active_threads", &total_profile))
+            .chain(
+                self.tags
+                    .iter()
+                    .filter_map(|tag| tags_profiles.get(tag).map(|p| (tag.as_str(), p))),
+            )
+            .collect();
+        write_profiles_csv(&profiles, path)
+    }
+
+    /// Save the log in the Trace Event Format, readable by chrome://tracing and Perfetto
+    /// (https://ui.perfetto.dev) which can smoothly zoom into very large traces.
+    /// Each task is a complete event on its thread's track, categorized by the tags of the
+    /// subgraphs containing it (with their work amounts as arguments).
+    /// Links between tasks on different threads are displayed as flow arrows.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let dir = std::env::temp_dir();
+    /// log.save_chrome_trace(dir.join("sum_trace.json")).expect("saving trace failed");
+    /// ```
+    pub fn save_chrome_trace<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_chrome_trace(self, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Save the tasks graph in graphviz DOT format.
+    /// Nodes are labelled with task id, thread and duration and subgraphs are clustered by tag.
+    /// Render it with `dot -Tsvg sum.dot -o sum_dag.svg` for instance.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::{join, subgraph, ThreadPoolBuilder};
+    ///
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| {
+    ///     subgraph("sum", 4, || {
+    ///         let (a, b) = join(|| 1 + 1, || 2 + 2);
+    ///         a + b
+    ///     })
+    /// });
+    /// let dir = std::env::temp_dir();
+    /// log.save_dot(dir.join("sum.dot")).expect("saving dot failed");
+    /// let dot = std::fs::read_to_string(dir.join("sum.dot")).expect("reading dot failed");
+    /// assert!(dot.starts_with("digraph"));
+    /// assert!(dot.contains("subgraph cluster_0"));
+    /// let edges: usize = log.tasks_logs.iter().map(|t| t.children.len()).sum();
+    /// assert_eq!(dot.matches("->").count(), edges);
+    /// ```
+    pub fn save_dot<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_dot(self, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Return for each stack of nested tags (outermost first, joined by ';') the total duration
+    /// of all tasks with this stack. Tasks outside of all subgraphs are under "_NO_TAGS_".
+    /// Stacks are sorted.
+    pub fn folded_stacks(&self) -> Vec<(String, TimeStamp)> {
+        folded_stacks(self)
+    }
+
+    /// Save the folded stacks ("outer;inner duration" lines, durations in ns),
+    /// ready for flamegraph.pl or inferno.
+    /// See `tasks_tag_stacks` for an example.
+    pub fn save_folded_stacks<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_folded_stacks(self, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Save the log as a speedscope (https://www.speedscope.app) file.
+    /// Each thread is an evented profile where tags of nested subgraphs are the frames.
+    /// See `tasks_tag_stacks` for an example.
+    pub fn save_speedscope<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_speedscope(self, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Check all structural invariants the display code relies on and return the broken ones.
+    /// Logs we record are always valid but hand-edited or externally generated ones might not be.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::{ThreadPoolBuilder, Violation};
+    ///
+    /// let v: Vec<u64> = (0..10_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, mut log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// assert!(log.validate().is_empty());
+    /// let tasks_number = log.tasks_logs.len();
+    /// log.tasks_logs[0].children.push(tasks_number);
+    /// assert_eq!(
+    ///     log.validate(),
+    ///     vec![Violation::ChildOutOfRange { task: 0, child: tasks_number }]
+    /// );
+    /// ```
+    pub fn validate(&self) -> Vec<Violation> {
+        violations(self)
+    }
+
     /// Load a rayon_logs log file and deserializes it into a `RunLog`.
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<RunLog, io::Error> {
-        let file = File::open(path).unwrap();
-        serde_json::from_reader(file).map_err(|_| ErrorKind::InvalidData.into())
+    /// Both json and binary formats are accepted (we look at the file's content, not its name).
+    /// Logs saved with older schema versions are migrated to the current one.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::{Error, RunLog};
+    ///
+    /// let dir = std::env::temp_dir();
+    /// std::fs::write(dir.join("broken_log.json"), "{\"threads_number\": 2,\n  \"tasks_logs\": [}")
+    ///     .expect("writing file failed");
+    /// match RunLog::load(dir.join("broken_log.json")) {
+    ///     Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (2, 18)),
+    ///     _ => panic!("we should not be able to parse this log"),
+    /// }
+    /// assert!(matches!(RunLog::load("no_such_log.json"), Err(Error::Io(_))));
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RunLog, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        if reader.fill_buf()?.starts_with(binary::MAGIC) {
+            reader.consume(binary::MAGIC.len());
+            Ok(binary::read_log(&mut reader)?)
+        } else {
+            let log: Value = serde_json::from_reader(reader)?;
+            let log: RunLog = serde_json::from_value(migrate(log)?)?;
+            Ok(log.fill_subgraphs_parents())
+        }
     }
 
-    /// Save an svg file of all logged information.
+    /// Save an (animated) svg file of all logged information.
+    /// Tasks display their labels on hover; use `save_html` for switching between tags.
     pub fn save_svg<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
-        let scene = visualisation(self);
-        write_svg_file(&scene, path)
+        self.save_svg_with_options(path, &SvgOptions::default())
+    }
+
+    /// Save an svg file of all logged information, rendered with given options.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::{SvgOptions, ThreadPoolBuilder};
+    ///
+    /// let v: Vec<u64> = (0..10_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let dir = std::env::temp_dir();
+    /// log.save_svg_with_options(dir.join("sum_static.svg"), &SvgOptions::static_svg())
+    ///     .expect("saving svg failed");
+    /// let svg = std::fs::read_to_string(dir.join("sum_static.svg")).expect("reading svg failed");
+    /// assert!(!svg.contains("<animate") && !svg.contains("<script"));
+    /// ```
+    pub fn save_svg_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &SvgOptions,
+    ) -> Result<(), io::Error> {
+        let scene = aggregated_visualisation(self, options.aggregation);
+        write_svg_file(&scene, path, options)
+    }
+
+    /// Save an svg file displaying the log as a Gantt chart: one lane per thread with tasks
+    /// and idle periods at their real times.
+    /// Dependencies between threads are drawn as arrows.
+    /// The chart is not aggregated: `options.aggregation` only applies to fork-join graphs.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::{SvgOptions, ThreadPoolBuilder};
+    ///
+    /// let v: Vec<u64> = (0..10_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let dir = std::env::temp_dir();
+    /// log.save_gantt_svg(dir.join("sum_gantt.svg"), &SvgOptions::static_svg())
+    ///     .expect("saving svg failed");
+    /// ```
+    pub fn save_gantt_svg<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &SvgOptions,
+    ) -> Result<(), io::Error> {
+        let scene = gantt(self);
+        write_svg_file(&scene, path, options)
+    }
+
+    /// Save a standalone html viewer for the log.
+    /// It displays both the fork-join graph and the gantt chart, with zoom and pan,
+    /// a time cursor, tags selection, search by task id or tag and details on hovered tasks.
+    /// Everything is embedded in the file which can be viewed offline.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::prelude::*;
+    /// use rayon_logs::ThreadPoolBuilder;
+    ///
+    /// let v: Vec<u64> = (0..10_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| v.par_iter().sum::<u64>());
+    /// let dir = std::env::temp_dir();
+    /// log.save_html(dir.join("sum.html")).expect("saving html failed");
+    /// let html = std::fs::read_to_string(dir.join("sum.html")).expect("reading html failed");
+    /// assert!(!html.contains("/*DATA*/") && !html.contains("http://cdn"));
+    /// ```
+    pub fn save_html<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_html(self, &mut writer)?;
+        writer.flush()
     }
 
     /// Save log file of currently recorded tasks logs.
+    /// The format is chosen from the file extension (see `LogFormat::from_path`).
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
-        let file = File::create(path)?;
-        serde_json::to_writer(file, &self).expect("failed serializing");
-        Ok(())
+        let format = LogFormat::from_path(&path);
+        self.save_as(path, format)
+    }
+
+    /// Save log file of currently recorded tasks logs in given format.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rayon_logs::{join, subgraph, LogFormat, RunLog, ThreadPoolBuilder};
+    ///
+    /// fn sum(slice: &[u64]) -> u64 {
+    ///     if slice.len() < 1_000 {
+    ///         subgraph("sum", slice.len(), || slice.iter().sum())
+    ///     } else {
+    ///         let (left, right) = slice.split_at(slice.len() / 2);
+    ///         let (sleft, sright) = join(|| sum(left), || sum(right));
+    ///         sleft + sright
+    ///     }
+    /// }
+    ///
+    /// let v: Vec<u64> = (0..100_000).collect();
+    /// let pool = ThreadPoolBuilder::new()
+    ///     .num_threads(2)
+    ///     .build()
+    ///     .expect("building pool failed");
+    /// let (_, log) = pool.logging_install(|| sum(&v));
+    /// let dir = std::env::temp_dir();
+    /// log.save_as(dir.join("sum_log.json"), LogFormat::Json).expect("saving json failed");
+    /// log.save_as(dir.join("sum_log.rlog"), LogFormat::Binary).expect("saving binary failed");
+    /// let json_log = RunLog::load(dir.join("sum_log.json")).expect("loading json failed");
+    /// let binary_log = RunLog::load(dir.join("sum_log.rlog")).expect("loading binary failed");
+    /// assert_eq!(json_log, log);
+    /// assert_eq!(binary_log, json_log);
+    /// ```
+    pub fn save_as<P: AsRef<Path>>(&self, path: P, format: LogFormat) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            LogFormat::Json => {
+                serde_json::to_writer(&mut writer, &self).expect("failed serializing")
+            }
+            LogFormat::Binary => binary::write_log(&mut writer, self)?,
+        }
+        writer.flush()
+    }
+}
+
+/// Replay execution, figuring out when each thread is idle.
+/// Returns (thread, start, end) for each idle period, sorted by starting time.
+pub(crate) fn idle_gaps(
+    tasks: &[TaskLog],
+    threads_number: usize,
+) -> Vec<(usize, TimeStamp, TimeStamp)> {
+    // do one pass to figure out the last recorded time.
+    // we need it to figure out who is idle at the end.
+    let last_time = tasks.iter().map(|t| t.end_time).max().unwrap();
+    let first_time = tasks.iter().map(|t| t.start_time).min().unwrap();
+
+    // sort everyone by time.
+    // we add fake tasks at the end for last idle periods.
+    let mut sorted_tasks: Vec<(usize, TimeStamp, TimeStamp)> = tasks
+        .iter()
+        .map(|t| (t.thread_id, t.start_time, t.end_time))
+        .chain((0..threads_number).map(|i| (i, last_time, last_time + 1)))
+        .collect();
+    sorted_tasks.sort_by_key(|t| t.1);
+
+    let mut previous_activities: Vec<TimeStamp> = repeat(first_time).take(threads_number).collect();
+    let mut gaps = Vec::new();
+    for (thread_id, start, end) in sorted_tasks {
+        let previous_end = previous_activities[thread_id];
+        if start > previous_end {
+            gaps.push((thread_id, previous_end, start));
+        }
+        previous_activities[thread_id] = end;
+    }
+    gaps
+}
+
+/// Keep only the events of a (possibly truncated) thread history which make sense on their own.
+/// Everything before the first task start belongs to a task whose start is lost so we skip it
+/// (except what idle threads do) and we also remove subgraphs whose start or end is missing.
+fn complete_events<'e, I: Iterator<Item = &'e RayonEvent>>(events: I) -> Vec<RayonEvent> {
+    let mut started = false;
+    let events: Vec<&RayonEvent> = events
+        .filter(|e| {
+            started |= matches!(e, RayonEvent::TaskStart(_, _));
+            started
+                || matches!(
+                    e,
+                    RayonEvent::FailedSteals(_, _)
+                        | RayonEvent::SearchStart(_)
+                        | RayonEvent::SearchEnd(_)
+                        | RayonEvent::SleepStart(_)
+                        | RayonEvent::SleepEnd(_)
+                )
+        })
+        .collect();
+    let mut open_subgraphs = Vec::new();
+    let mut incomplete_subgraphs = HashSet::new();
+    for (index, event) in events.iter().enumerate() {
+        if let RayonEvent::SubgraphStart(_) = event {
+            open_subgraphs.push(index);
+        } else if let RayonEvent::SubgraphEnd(_, _) = event {
+            if open_subgraphs.pop().is_none() {
+                incomplete_subgraphs.insert(index);
+            }
+        }
+    }
+    incomplete_subgraphs.extend(open_subgraphs);
+    events
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !incomplete_subgraphs.contains(index))
+        .map(|(_, e)| e.clone())
+        .collect()
+}
+
+#[cfg(test)]
+impl RunLog {
+    /// Build a log running given (start, end, thread, children) tasks on given number of threads.
+    /// It lasts from the first start to the last end and has no tags, subgraphs, steals nor
+    /// idle periods.
+    pub(crate) fn from_tasks(
+        threads_number: usize,
+        tasks: &[(TimeStamp, TimeStamp, usize, &[TaskId])],
+    ) -> RunLog {
+        let first_time = tasks.iter().map(|t| t.0).min().unwrap_or(0);
+        let last_time = tasks.iter().map(|t| t.1).max().unwrap_or(0);
+        RunLog {
+            threads_number,
+            tasks_logs: tasks
+                .iter()
+                .map(|&(start_time, end_time, thread_id, children)| TaskLog {
+                    start_time,
+                    end_time,
+                    thread_id,
+                    children: children.to_vec(),
+                })
+                .collect(),
+            duration: last_time - first_time,
+            tags: Vec::new(),
+            subgraphs: Vec::new(),
+            subgraphs_parents: Vec::new(),
+            steals: Vec::new(),
+            idle_periods: Vec::new(),
+            partial: false,
+            metadata: Metadata::unknown(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_events::RayonEvent::*;
+    use crate::storage::Record;
+
+    /// Build a log from given events of each thread (time starts at 0).
+    fn log_from(threads_events: &[Vec<RayonEvent>]) -> RunLog {
+        let tasks_number = threads_events
+            .iter()
+            .flatten()
+            .filter_map(|e| match *e {
+                TaskStart(task, _) => Some(task + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        RunLog::from_events(
+            tasks_number,
+            0,
+            threads_events.iter().map(|events| events.iter()).collect(),
+            0,
+        )
+        .expect("invalid events")
+    }
+
+    /// Task 0 forks tasks 1 (on thread 0) and 2 (stolen by thread 1), joined by task 3.
+    fn fork_join_events() -> Vec<Vec<RayonEvent>> {
+        vec![
+            vec![
+                TaskStart(0, 0),
+                Child(1),
+                Child(2),
+                TaskEnd(10),
+                TaskStart(1, 10),
+                Child(3),
+                TaskEnd(100),
+                TaskStart(3, 200),
+                TaskEnd(210),
+            ],
+            vec![
+                FailedSteals(5, 1),
+                FailedSteals(15, 3),
+                Steal(2, 20),
+                TaskStart(2, 20),
+                Child(3),
+                TaskEnd(200),
+            ],
+        ]
+    }
+
+    #[test]
+    fn failed_steals_are_aggregated() {
+        let log = log_from(&fork_join_events());
+        assert_eq!(log.successful_steals(), 1);
+        assert_eq!(log.failed_steals(), 4);
+        let steal = log.steals.iter().find(|s| s.successful()).unwrap();
+        assert_eq!(
+            (steal.thief, steal.victim, steal.task),
+            (1, Some(0), Some(2))
+        );
+    }
+
+    #[test]
+    fn subgraphs_parents_are_recorded() {
+        let log = log_from(&[
+            vec![
+                TaskStart(0, 0),
+                SubgraphStart("outer"),
+                Child(1),
+                Child(2),
+                TaskEnd(10),
+                TaskStart(1, 10),
+                SubgraphStart("left"),
+                SubgraphEnd("left", 1),
+                Child(3),
+                TaskEnd(100),
+                TaskStart(3, 200),
+                SubgraphEnd("outer", 4),
+                SubgraphStart("after"),
+                SubgraphEnd("after", 5),
+                TaskEnd(210),
+            ],
+            vec![
+                Steal(2, 20),
+                TaskStart(2, 20),
+                SubgraphStart("right"),
+                SubgraphEnd("right", 2),
+                Child(3),
+                TaskEnd(200),
+            ],
+        ]);
+        assert_eq!(log.tags, vec!["outer", "left", "right", "after"]);
+        assert_eq!(
+            log.subgraphs,
+            vec![(0, 3, 0, 4), (1, 1, 1, 1), (2, 2, 2, 2), (3, 3, 3, 5)]
+        );
+        // concurrent subgraphs both belong to the outer one
+        assert_eq!(log.subgraphs_parents, vec![None, Some(0), Some(0), None]);
+        assert_eq!(log.tasks_tag_stacks()[2], vec!["outer", "right"]);
+    }
+
+    #[test]
+    fn partial_histories_are_completed() {
+        let storages: Vec<Arc<Storage<RayonEvent>>> = vec![
+            vec![
+                SubgraphEnd("lost", 3),
+                TaskEnd(50),
+                TaskStart(107, 100),
+                Child(108),
+                TaskEnd(110),
+                TaskStart(108, 110),
+                TaskEnd(120),
+            ],
+            vec![FailedSteals(20, 2), TaskEnd(60), FailedSteals(105, 1)],
+        ]
+        .into_iter()
+        .map(|events| {
+            let storage = Storage::new();
+            events.into_iter().for_each(|e| storage.push(e));
+            Arc::new(storage)
+        })
+        .collect();
+        let log = RunLog::from_partial_events(&storages).expect("invalid partial log");
+        assert_eq!(log.tasks_logs.len(), 2);
+        assert_eq!(log.duration, 20);
+        assert_eq!(log.tasks_logs[0].start_time, 0);
+        assert_eq!(log.tasks_logs[0].children, vec![1]);
+        assert!(log.subgraphs.is_empty());
+        // the failed steals from before the first recorded task are dropped
+        assert_eq!(log.failed_steals(), 1);
+        assert_eq!(log.steals[0].time, 5);
+    }
+
+    #[test]
+    fn events_before_the_start_are_rejected() {
+        let events = [FailedSteals(5, 1), TaskStart(0, 10), TaskEnd(20)];
+        let log = RunLog::from_events(1, 0, vec![events.iter()], 8);
+        assert!(matches!(log, Err(Error::Inconsistent { thread: 0, .. })));
+    }
+
+    #[test]
+    fn logs_without_tasks_are_rejected() {
+        let events = [FailedSteals(5, 1)];
+        let log = RunLog::from_events(0, 0, vec![events.iter()], 0);
+        assert!(matches!(log, Err(Error::Inconsistent { task: None, .. })));
+    }
+
+    #[test]
+    fn streamed_logs_start_with_their_first_event() {
+        let directory = std::env::temp_dir().join("rayon_logs_streamed_start");
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut file = File::create(directory.join("thread_0.bin")).unwrap();
+        for event in &[FailedSteals(5, 1), TaskStart(0, 10), TaskEnd(20)] {
+            event.write(&mut file).unwrap();
+        }
+        let log = RunLog::load_streamed(&directory).expect("loading streamed log failed");
+        assert_eq!(log.steals[0].time, 0);
+        assert_eq!(log.tasks_logs[0].start_time, 5);
+    }
+
+    #[test]
+    fn idle_periods_are_recorded() {
+        let log = log_from(&[
+            vec![
+                SleepEnd(0),
+                SearchEnd(0),
+                TaskStart(0, 0),
+                Child(1),
+                Child(2),
+                TaskEnd(1_000),
+                TaskStart(1, 1_000),
+                Child(3),
+                TaskEnd(300_000),
+                TaskStart(3, 300_000),
+                TaskEnd(301_000),
+                SearchStart(301_000),
+                SleepStart(400_000),
+            ],
+            vec![
+                SearchStart(0),
+                FailedSteals(10, 1),
+                SearchEnd(1_000),
+                Steal(2, 1_000),
+                TaskStart(2, 1_000),
+                Child(3),
+                TaskEnd(100_000),
+                SearchStart(100_000),
+                FailedSteals(100_010, 1),
+                FailedSteals(120_000, 50),
+                SleepStart(140_000),
+                SleepEnd(200_000),
+                SearchEnd(210_000),
+                SearchStart(250_000),
+            ],
+        ]);
+        let periods: Vec<_> = log
+            .idle_periods
+            .iter()
+            .map(|p| (p.thread_id, p.start, p.end, p.kind))
+            .collect();
+        assert_eq!(
+            periods,
+            vec![
+                (1, 0, 1_000, IdleKind::Searching),
+                (1, 100_000, 140_000, IdleKind::Searching),
+                (1, 140_000, 200_000, IdleKind::Sleeping),
+                (1, 200_000, 210_000, IdleKind::Searching),
+                // still searching at the end
+                (1, 250_000, 301_000, IdleKind::Searching),
+            ]
+        );
+        assert_eq!(log.idle_time(IdleKind::Sleeping), 60_000);
+        assert_eq!(log.idle_time(IdleKind::Searching), 102_000);
+        assert_eq!(log.failed_steals(), 52);
     }
 }
@@ -0,0 +1,121 @@
+//! Information about the context in which a log was produced.
+//! It is stored in each `RunLog` so that log files are self-describing.
+use crate::Error;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current version of the logs schema.
+/// Increase it whenever the layout of saved logs changes and add the corresponding
+/// migration in `migrate`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Description of the clock used for all timestamps.
+const CLOCK: &str = "monotonic (std::time::Instant), nanoseconds";
+
+/// Where and how a log was produced.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Metadata {
+    /// version of the logs schema (see `SCHEMA_VERSION`).
+    pub schema_version: u32,
+    /// version of rayon_logs which produced the log.
+    pub crate_version: String,
+    /// name of the machine we ran on.
+    pub hostname: String,
+    /// number of cpus available on the machine.
+    pub cpus_number: usize,
+    /// wall-clock time at which the run started (in ns since the unix epoch).
+    pub start_time: u64,
+    /// command line of the logged program.
+    pub command_line: Vec<String>,
+    /// which clock produced the timestamps.
+    pub clock: String,
+    /// user supplied values (see `ThreadPoolBuilder::metadata`).
+    pub user: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// Gather information about the current process, for a run starting at given wall-clock time.
+    pub(crate) fn collect(user: &BTreeMap<String, String>, start_time: SystemTime) -> Self {
+        Metadata {
+            schema_version: SCHEMA_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: hostname(),
+            cpus_number: cpus_number(),
+            start_time: start_time
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0),
+            command_line: env::args().collect(),
+            clock: CLOCK.to_string(),
+            user: user.clone(),
+        }
+    }
+
+    /// Metadata for logs saved before we had any: everything is unknown.
+    pub(crate) fn unknown() -> Self {
+        Metadata {
+            schema_version: SCHEMA_VERSION,
+            ..Default::default()
+        }
+    }
+}
+
+/// Return the name of the machine we run on (empty if unknown).
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    let status =
+        unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if status != 0 {
+        return String::new();
+    }
+    let length = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..length]).into_owned()
+}
+
+/// Return the name of the machine we run on (empty if unknown).
+#[cfg(not(unix))]
+fn hostname() -> String {
+    env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+/// Return the number of cpus we can use.
+fn cpus_number() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Return the schema version of given (json) log.
+/// Logs without metadata are version 0.
+fn schema_version(log: &Value) -> u64 {
+    log.get("metadata")
+        .and_then(|m| m.get("schema_version"))
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+}
+
+/// Upgrade given (json) log to the current schema version.
+/// Invalid logs are left untouched, deserializing them will fail.
+pub(crate) fn migrate(mut log: Value) -> Result<Value, Error> {
+    let version = schema_version(&log);
+    if version > u64::from(SCHEMA_VERSION) {
+        return Err(Error::UnsupportedSchema(version));
+    }
+    if let Some(fields) = log.as_object_mut() {
+        if version == 0 {
+            // version 0 had no steals, no idle periods, no partial logs, no metadata
+            // and no subgraphs parents (they are re-computed after loading)
+            fields.entry("steals").or_insert_with(|| json!([]));
+            fields.entry("idle_periods").or_insert_with(|| json!([]));
+            fields.entry("partial").or_insert(Value::Bool(false));
+            fields.insert("metadata".to_string(), json!(Metadata::unknown()));
+            fields.insert("subgraphs_parents".to_string(), json!([]));
+        }
+        fields["metadata"]["schema_version"] = json!(SCHEMA_VERSION);
+    }
+    Ok(log)
+}
@@ -9,22 +9,33 @@ use perfcnt::linux::{CacheId, CacheOpId, CacheOpResultId, HardwareEventType, Sof
 #[cfg(feature = "perf")]
 use perfcnt::{AbstractPerfCounter, PerfCounter};
 
+use crate::error::Error;
 use crate::log::RunLog;
-use crate::raw_events::{now, RayonEvent, TaskId};
+use crate::metadata::Metadata;
+use crate::raw_events::{now, RayonEvent, TaskId, TimeStamp};
 use crate::storage::Storage;
 use crate::Comparator;
 use crate::{scope, scope_fifo, Scope, ScopeFifo};
 use rayon;
 use rayon::FnContext;
-use std::cell::RefCell;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fs::remove_file;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{spin_loop_hint, AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 /// We use an atomic usize to generate unique ids for tasks.
 pub(crate) static NEXT_TASK_ID: AtomicUsize = AtomicUsize::new(0);
 /// We use an atomic usize to generate unique ids for iterators.
 pub(crate) static NEXT_ITERATOR_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// Spinning threads fail stealing in a tight loop so we record at most one failed steal
+/// event per period (in ns and per thread), counting all attempts.
+const FAILED_STEALS_PERIOD: TimeStamp = 25_000;
+
 /// get an id for a new task and increment global tasks counter.
 pub fn next_task_id() -> TaskId {
     NEXT_TASK_ID.fetch_add(1, Ordering::SeqCst)
@@ -36,6 +47,10 @@ pub fn next_iterator_id() -> usize {
 }
 
 thread_local!(pub(crate) static LOGS: RefCell<Arc<Storage<RayonEvent>>> = RefCell::new(Arc::new(Storage::new())));
+thread_local!(
+    /// recording epoch, time of the last recorded failed steal and number of attempts since then.
+    static FAILED_STEALS: Cell<(usize, TimeStamp, usize)> = const { Cell::new((0, 0, 0)) }
+);
 
 /// Add given event to logs of current thread.
 pub(crate) fn log(event: RayonEvent) {
@@ -53,6 +68,98 @@ macro_rules! logs {
     }
 }
 
+/// Records what the idle threads of a pool do (failed steals, searching and sleeping).
+/// It is shared with the rayon callbacks of the pool's threads.
+#[derive(Default)]
+pub(crate) struct IdleRecorder {
+    /// Idle threads are only recorded inside `logging_install`.
+    recording: AtomicBool,
+    /// How many threads are currently logging an idle event.
+    logging: AtomicUsize,
+    /// Incremented each time we start recording so that threads forget attempts
+    /// from previous recordings.
+    epoch: AtomicUsize,
+}
+
+impl IdleRecorder {
+    /// Log events on current thread with given closure, if we are recording.
+    /// Idle threads keep on stealing and sleeping after computations end so we need to make
+    /// sure nobody writes in the logs while we post-process them.
+    fn record<F: FnOnce()>(&self, log_events: F) {
+        self.logging.fetch_add(1, Ordering::SeqCst);
+        if self.recording.load(Ordering::SeqCst) {
+            log_events();
+        }
+        self.logging.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Log a failed steal on current thread (called from rayon's steal callback).
+    /// Attempts closer than `FAILED_STEALS_PERIOD` to the last recorded one are only counted:
+    /// they get recorded together with the next one (attempts after the last recorded one
+    /// of a run are lost).
+    pub(crate) fn log_failed_steal(&self) {
+        self.record(|| {
+            let time = now();
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            FAILED_STEALS.with(|failed_steals| {
+                // first attempt of a recording is always recorded
+                let pending = match failed_steals.get() {
+                    (last_epoch, last_time, attempts) if last_epoch == epoch => {
+                        Some((last_time, attempts))
+                    }
+                    _ => None,
+                };
+                match pending {
+                    Some((last_time, attempts))
+                        if time.saturating_sub(last_time) < FAILED_STEALS_PERIOD =>
+                    {
+                        failed_steals.set((epoch, last_time, attempts + 1))
+                    }
+                    _ => {
+                        let attempts = pending.map_or(0, |(_, attempts)| attempts);
+                        log(RayonEvent::FailedSteals(time, attempts + 1));
+                        failed_steals.set((epoch, time, 0));
+                    }
+                }
+            })
+        })
+    }
+
+    /// Log current thread entering or leaving its steal loop or its sleep
+    /// (called from rayon's hooks, see `ThreadPoolBuilder::build`).
+    pub(crate) fn log_transition(&self, transition: fn(TimeStamp) -> RayonEvent) {
+        self.record(|| log(transition(now())))
+    }
+
+    /// Start recording idle threads.
+    fn start(&self) {
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        self.recording.store(true, Ordering::SeqCst);
+    }
+
+    /// Stop recording idle threads and wait for all threads currently logging.
+    fn stop(&self) {
+        self.recording.store(false, Ordering::SeqCst);
+        while self.logging.load(Ordering::SeqCst) != 0 {
+            spin_loop_hint();
+        }
+    }
+}
+
+/// Log the start of the second task of a fork, together with a steal
+/// if it migrated to another thread.
+pub(crate) fn start_forked_task(task: TaskId, migrated: bool) {
+    let start = now();
+    if migrated {
+        logs!(
+            RayonEvent::Steal(task, start),
+            RayonEvent::TaskStart(task, start)
+        );
+    } else {
+        log(RayonEvent::TaskStart(task, start));
+    }
+}
+
 /// We tag all the tasks that op makes as one subgraph.
 ///
 /// `work_type` is a str tag and `work_amount` an integer specifying the expected algorithmic cost
@@ -93,9 +200,9 @@ macro_rules! logs {
 /// </div>
 ///
 /// Using it we obtain the graph below.
-/// On the real file you can hover but javascript and toggle the display of the different tags but
-/// it is disabled with rustdoc so I downgraded the file
-/// for this display.
+/// On the real (animated) svg file you can hover on tasks to see their labels;
+/// toggling the display of the different tags needs javascript so it is only available
+/// in the html viewer (see `RunLog::save_html`).
 pub fn subgraph<OP, R>(work_type: &'static str, work_amount: usize, op: OP) -> R
 where
     OP: FnOnce() -> R,
@@ -360,8 +467,8 @@ where
     };
 
     let id_b = next_task_id();
-    let cb = |c| {
-        log(RayonEvent::TaskStart(id_b, now()));
+    let cb = |c: FnContext| {
+        start_forked_task(id_b, c.migrated());
         let result = oper_b(c);
         logs!(RayonEvent::Child(id_c), RayonEvent::TaskEnd(now()));
         result
@@ -474,8 +581,8 @@ where
     };
 
     let id_b = next_task_id();
-    let cb = || {
-        log(RayonEvent::TaskStart(id_b, now()));
+    let cb = |c: FnContext| {
+        start_forked_task(id_b, c.migrated());
         let result = oper_b();
         logs!(RayonEvent::Child(id_c), RayonEvent::TaskEnd(now()));
         result
@@ -486,7 +593,8 @@ where
         RayonEvent::Child(id_b),
         RayonEvent::TaskEnd(now())
     );
-    let r = rayon::join(ca, cb);
+    // we go through join_context to figure out if b is stolen
+    let r = rayon::join_context(|_| ca(), cb);
     log(RayonEvent::TaskStart(id_c, now()));
     r
 }
@@ -498,6 +606,23 @@ static INSTALL_COUNT: AtomicUsize = AtomicUsize::new(0);
 pub struct ThreadPool {
     pub(crate) logs: Arc<Mutex<Vec<Arc<Storage<RayonEvent>>>>>,
     pub(crate) pool: rayon::ThreadPool,
+    /// we keep the history accross installs
+    pub(crate) flight_recorder: bool,
+    /// user supplied metadata added to all logs
+    pub(crate) metadata: BTreeMap<String, String>,
+    /// lock file we hold on the streaming directory (if any)
+    /// or why we could not get it
+    pub(crate) stream_lock: Result<Option<PathBuf>, (io::ErrorKind, String)>,
+    /// what idle threads do, recorded by our threads' rayon callbacks
+    pub(crate) idle_recorder: Arc<IdleRecorder>,
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        if let Ok(Some(ref lock)) = self.stream_lock {
+            remove_file(lock).ok();
+        }
+    }
 }
 
 impl ThreadPool {
@@ -514,12 +639,72 @@ impl ThreadPool {
     /// Execute given closure in the thread pool, logging it's task as the initial one.
     /// After running, we post-process the logs and return a `RunLog` together with the closure's
     /// result.
+    ///
+    /// For flight recorders the returned log is `flight_recorder_log`'s recent history
+    /// (which does not start with the closure's task).
+    ///
+    /// Panics if logs cannot be post-processed (see `try_logging_install`).
     pub fn logging_install<OP, R>(&self, op: OP) -> (R, RunLog)
     where
         OP: FnOnce() -> R + Send,
         R: Send,
     {
+        self.try_logging_install(op)
+            .unwrap_or_else(|e| panic!("failed building logs: {}", e))
+    }
+
+    /// Like `logging_install` but returns an error (instead of panicking) if logs cannot be
+    /// post-processed.
+    /// This happens for example if you mix logged and un-logged computations, if streamed logs
+    /// cannot be read back or if the streaming directory is in use by another pool
+    /// (the closure is not run in this last case).
+    /// The closure's result is lost in this case.
+    pub fn try_logging_install<OP, R>(&self, op: OP) -> Result<(R, RunLog), Error>
+    where
+        OP: FnOnce() -> R + Send,
+        R: Send,
+    {
+        if let Err((kind, ref message)) = self.stream_lock {
+            return Err(io::Error::new(kind, message.clone()).into());
+        }
+        if self.flight_recorder {
+            let r = self.record(op);
+            return self.flight_recorder_log().map(|log| (r, log));
+        }
         self.reset();
+        let start = now();
+        let wall_clock_start = SystemTime::now();
+        let r = self.record(op);
+        let logs = &*self.logs.lock().unwrap();
+        let streamed_logs: Vec<&Path> = logs.iter().filter_map(|l| l.sink_path()).collect();
+        let mut log = if streamed_logs.is_empty() {
+            RunLog::new(
+                NEXT_TASK_ID.load(Ordering::Relaxed),
+                NEXT_ITERATOR_ID.load(Ordering::Relaxed),
+                logs,
+                start,
+            )?
+        } else {
+            for thread_logs in logs {
+                thread_logs.flush()?;
+            }
+            RunLog::from_streams(
+                NEXT_TASK_ID.load(Ordering::Relaxed),
+                NEXT_ITERATOR_ID.load(Ordering::Relaxed),
+                &streamed_logs,
+                start,
+            )?
+        };
+        log.metadata = Metadata::collect(&self.metadata, wall_clock_start);
+        Ok((r, log))
+    }
+
+    /// Run given closure in the pool, logging its task as the initial one.
+    fn record<OP, R>(&self, op: OP) -> R
+    where
+        OP: FnOnce() -> R + Send,
+        R: Send,
+    {
         let id = next_task_id();
         let c = || {
             log(RayonEvent::TaskStart(id, now()));
@@ -527,15 +712,21 @@ impl ThreadPool {
             log(RayonEvent::TaskEnd(now()));
             result
         };
-        let start = now();
+        self.idle_recorder.start();
         let r = self.pool.install(c);
-        let log = RunLog::new(
-            NEXT_TASK_ID.load(Ordering::Relaxed),
-            NEXT_ITERATOR_ID.load(Ordering::Relaxed),
-            &*self.logs.lock().unwrap(),
-            start,
-        );
-        (r, log)
+        self.idle_recorder.stop();
+        r
+    }
+
+    /// Return a log of the recent history recorded by the flight recorder
+    /// (see `ThreadPoolBuilder::flight_recorder`).
+    /// Do not call it while logged computations are running.
+    pub fn flight_recorder_log(&self) -> Result<RunLog, Error> {
+        let mut log = RunLog::from_partial_events(&self.logs.lock().unwrap())?;
+        // approximation: we consider the recorded history ends now
+        let wall_clock_start = SystemTime::now() - Duration::from_nanos(log.duration);
+        log.metadata = Metadata::collect(&self.metadata, wall_clock_start);
+        Ok(log)
     }
 
     /// Creates a scope that executes within this thread-pool.
@@ -563,11 +754,16 @@ impl ThreadPool {
 
     /// Execute given closure in the thread pool, logging it's task as the initial one.
     /// After running, we save a json file with filename being an incremental counter.
+    /// Flight recorders (see `ThreadPoolBuilder::flight_recorder`) only record events:
+    /// no log is built nor saved until you call `flight_recorder_log`.
     pub fn install<OP, R>(&self, op: OP) -> R
     where
         OP: FnOnce() -> R + Send,
         R: Send,
     {
+        if self.flight_recorder {
+            return self.record(op);
+        }
         let (r, log) = self.logging_install(op);
         log.save(format!(
             "log_{}.json",
@@ -582,3 +778,55 @@ impl ThreadPool {
         Comparator::new(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Failed steals logged so far by current thread.
+    fn failed_steals() -> Vec<usize> {
+        LOGS.with(|l| {
+            l.borrow()
+                .iter()
+                .filter_map(|e| match *e {
+                    RayonEvent::FailedSteals(_, attempts) => Some(attempts),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn failed_steals_are_forgotten_between_recordings() {
+        let recorder = IdleRecorder::default();
+        recorder.log_failed_steal();
+        assert!(failed_steals().is_empty());
+        recorder.start();
+        for _ in 0..10 {
+            recorder.log_failed_steal();
+        }
+        recorder.stop();
+        let first_run = failed_steals();
+        assert_eq!(first_run[0], 1);
+        recorder.start();
+        recorder.log_failed_steal();
+        recorder.stop();
+        // whatever was left pending is not counted in the new recording
+        assert_eq!(failed_steals()[first_run.len()..], [1]);
+    }
+
+    #[test]
+    fn idle_transitions_are_only_logged_while_recording() {
+        let recorder = IdleRecorder::default();
+        recorder.log_transition(RayonEvent::SleepEnd);
+        recorder.start();
+        recorder.log_transition(RayonEvent::SearchStart);
+        recorder.log_transition(RayonEvent::SearchEnd);
+        recorder.stop();
+        recorder.log_transition(RayonEvent::SleepStart);
+        let events: Vec<RayonEvent> = LOGS.with(|l| l.borrow().iter().cloned().collect());
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], RayonEvent::SearchStart(_)));
+        assert!(matches!(events[1], RayonEvent::SearchEnd(_)));
+    }
+}
@@ -0,0 +1,101 @@
+//! Parallelism profiles: how many threads are running tasks at each time.
+use crate::log::TaskLog;
+use crate::raw_events::TimeStamp;
+use itertools::Itertools;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// A step function: each point is a time and the number of active threads from this time on.
+/// Last point always has 0 active threads.
+pub type Profile = Vec<(TimeStamp, usize)>;
+
+/// Compute the parallelism profile of given tasks.
+/// Tasks overlapping on a thread only count once.
+pub(crate) fn parallelism_profile<'a, I: Iterator<Item = &'a TaskLog>>(tasks: I) -> Profile {
+    // first figure out activity intervals for each thread
+    let activities = tasks
+        .filter(|t| t.end_time > t.start_time)
+        .map(|t| (t.thread_id, (t.start_time, t.end_time)))
+        .into_group_map()
+        .into_values()
+        .flat_map(|intervals| {
+            intervals
+                .into_iter()
+                .sorted()
+                .coalesce(|(s1, e1), (s2, e2)| {
+                    if s2 <= e1 {
+                        Ok((s1, e1.max(e2)))
+                    } else {
+                        Err(((s1, e1), (s2, e2)))
+                    }
+                })
+        });
+    // now count
+    let mut changes: Vec<(TimeStamp, isize)> = activities
+        .flat_map(|(start, end)| vec![(start, 1), (end, -1)])
+        .collect();
+    changes.sort();
+    let mut active = 0;
+    changes
+        .into_iter()
+        .group_by(|&(time, _)| time)
+        .into_iter()
+        .map(|(time, changes)| {
+            active += changes.map(|(_, c)| c).sum::<isize>();
+            (time, active as usize)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .dedup_by(|(_, a1), (_, a2)| a1 == a2)
+        .collect()
+}
+
+/// Return the value of given profile at given time.
+fn value_at(profile: &[(TimeStamp, usize)], time: TimeStamp) -> usize {
+    match profile.binary_search_by_key(&time, |&(t, _)| t) {
+        Ok(index) => profile[index].1,
+        Err(0) => 0,
+        Err(index) => profile[index - 1].1,
+    }
+}
+
+/// Quote given string if needed.
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Save given profiles (with their names) as csv, one column per profile.
+/// We have one line for each time at which one of the profiles changes.
+pub(crate) fn write_profiles_csv<P: AsRef<Path>>(
+    profiles: &[(&str, &Profile)],
+    path: P,
+) -> Result<(), io::Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(
+        file,
+        "time,{}",
+        profiles.iter().map(|(name, _)| csv_field(name)).join(",")
+    )?;
+    let times = profiles
+        .iter()
+        .map(|(_, profile)| profile.iter().map(|&(time, _)| time))
+        .kmerge()
+        .dedup();
+    for time in times {
+        writeln!(
+            file,
+            "{},{}",
+            time,
+            profiles
+                .iter()
+                .map(|(_, profile)| value_at(profile, time))
+                .join(",")
+        )?;
+    }
+    file.flush()
+}
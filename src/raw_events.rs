@@ -2,7 +2,11 @@
 //! Events which are very fast to log and logged on a per thread basis.
 //! These events will be post-processed after execution in order to generate
 //! a tasks graph.
+use crate::storage::Record;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
 
 /// unique task identifier
 pub(crate) type TaskId = usize;
@@ -14,6 +18,8 @@ pub(crate) type TimeStamp = u64;
 use lazy_static::lazy_static;
 lazy_static! {
     static ref START_TIME: std::time::Instant = std::time::Instant::now();
+    /// tags read back from disk (we need them to live forever).
+    static ref READ_TAGS: Mutex<HashMap<String, &'static str>> = Mutex::new(HashMap::new());
 }
 
 /// Return number of nano seconds since start.
@@ -22,7 +28,7 @@ pub(crate) fn now() -> TimeStamp {
 }
 
 /// All types of raw events we can log.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) enum RayonEvent {
     /// A task starts.
     TaskStart(TaskId, TimeStamp),
@@ -34,6 +40,20 @@ pub(crate) enum RayonEvent {
     SubgraphStart(&'static str),
     /// End a subgraph and register a work amount.
     SubgraphEnd(&'static str, usize),
+    /// Given task is about to start on a thread which stole it.
+    Steal(TaskId, TimeStamp),
+    /// Current thread looked for some work to steal and found none.
+    /// Attempts are aggregated: we store how many of them happened since the previous
+    /// recorded one (included).
+    FailedSteals(TimeStamp, usize),
+    /// Current thread has no work left and starts looking for some to steal.
+    SearchStart(TimeStamp),
+    /// Current thread stops looking for work (it found some or it does not need any).
+    SearchEnd(TimeStamp),
+    /// Current thread falls asleep (waiting on rayon's condition variable).
+    SleepStart(TimeStamp),
+    /// Current thread wakes up (and goes back to searching).
+    SleepEnd(TimeStamp),
 }
 
 impl RayonEvent {
@@ -42,7 +62,175 @@ impl RayonEvent {
         match *self {
             RayonEvent::TaskStart(_, t) => t,
             RayonEvent::TaskEnd(t) => t,
+            RayonEvent::Steal(_, t) => t,
+            RayonEvent::FailedSteals(t, _) => t,
+            RayonEvent::SearchStart(t) => t,
+            RayonEvent::SearchEnd(t) => t,
+            RayonEvent::SleepStart(t) => t,
+            RayonEvent::SleepEnd(t) => t,
             _ => 0,
         }
     }
 }
+
+/// Return a static str equal to given tag.
+/// Each different tag is leaked only once.
+fn static_tag(tag: String) -> &'static str {
+    let mut read_tags = READ_TAGS.lock().unwrap();
+    read_tags
+        .entry(tag.clone())
+        .or_insert_with(|| Box::leak(tag.into_boxed_str()))
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn write_tag<W: Write>(writer: &mut W, tag: &str) -> io::Result<()> {
+    write_u64(writer, tag.len() as u64)?;
+    writer.write_all(tag.as_bytes())
+}
+
+fn read_tag<R: Read>(reader: &mut R) -> io::Result<&'static str> {
+    // do not trust the length for allocating
+    let length = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes)
+        .map(static_tag)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Raw events are streamed to disk as a one byte event type followed by
+/// little endian fields.
+impl Record for RayonEvent {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            RayonEvent::TaskStart(task, time) => {
+                writer.write_all(&[0])?;
+                write_u64(writer, task as u64)?;
+                write_u64(writer, time)
+            }
+            RayonEvent::TaskEnd(time) => {
+                writer.write_all(&[1])?;
+                write_u64(writer, time)
+            }
+            RayonEvent::Child(task) => {
+                writer.write_all(&[2])?;
+                write_u64(writer, task as u64)
+            }
+            RayonEvent::SubgraphStart(tag) => {
+                writer.write_all(&[3])?;
+                write_tag(writer, tag)
+            }
+            RayonEvent::SubgraphEnd(tag, work_amount) => {
+                writer.write_all(&[4])?;
+                write_tag(writer, tag)?;
+                write_u64(writer, work_amount as u64)
+            }
+            RayonEvent::Steal(task, time) => {
+                writer.write_all(&[5])?;
+                write_u64(writer, task as u64)?;
+                write_u64(writer, time)
+            }
+            RayonEvent::FailedSteals(time, attempts) => {
+                writer.write_all(&[6])?;
+                write_u64(writer, time)?;
+                write_u64(writer, attempts as u64)
+            }
+            RayonEvent::SearchStart(time) => {
+                writer.write_all(&[7])?;
+                write_u64(writer, time)
+            }
+            RayonEvent::SearchEnd(time) => {
+                writer.write_all(&[8])?;
+                write_u64(writer, time)
+            }
+            RayonEvent::SleepStart(time) => {
+                writer.write_all(&[9])?;
+                write_u64(writer, time)
+            }
+            RayonEvent::SleepEnd(time) => {
+                writer.write_all(&[10])?;
+                write_u64(writer, time)
+            }
+        }
+    }
+
+    fn read<R: Read>(reader: &mut R) -> io::Result<Option<Self>> {
+        let mut event_type = [0];
+        if reader.read(&mut event_type)? == 0 {
+            return Ok(None);
+        }
+        let event = match event_type[0] {
+            0 => RayonEvent::TaskStart(read_u64(reader)? as TaskId, read_u64(reader)?),
+            1 => RayonEvent::TaskEnd(read_u64(reader)?),
+            2 => RayonEvent::Child(read_u64(reader)? as TaskId),
+            3 => RayonEvent::SubgraphStart(read_tag(reader)?),
+            4 => RayonEvent::SubgraphEnd(read_tag(reader)?, read_u64(reader)? as usize),
+            5 => RayonEvent::Steal(read_u64(reader)? as TaskId, read_u64(reader)?),
+            6 => RayonEvent::FailedSteals(read_u64(reader)?, read_u64(reader)? as usize),
+            7 => RayonEvent::SearchStart(read_u64(reader)?),
+            8 => RayonEvent::SearchEnd(read_u64(reader)?),
+            9 => RayonEvent::SleepStart(read_u64(reader)?),
+            10 => RayonEvent::SleepEnd(read_u64(reader)?),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown raw event type",
+                ))
+            }
+        };
+        Ok(Some(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_round_trip() {
+        let events = vec![
+            RayonEvent::TaskStart(3, 12),
+            RayonEvent::SubgraphStart("sort"),
+            RayonEvent::Child(4),
+            RayonEvent::SubgraphEnd("sort", 1_000),
+            RayonEvent::Steal(4, 20),
+            RayonEvent::FailedSteals(25, 7),
+            RayonEvent::TaskEnd(30),
+            RayonEvent::SearchStart(31),
+            RayonEvent::SleepStart(40),
+            RayonEvent::SleepEnd(50),
+            RayonEvent::SearchEnd(55),
+        ];
+        let mut bytes = Vec::new();
+        for event in &events {
+            event.write(&mut bytes).unwrap();
+        }
+        let mut reader = &bytes[..];
+        let mut read_back = Vec::new();
+        while let Some(event) = RayonEvent::read(&mut reader).unwrap() {
+            read_back.push(event);
+        }
+        assert_eq!(format!("{:?}", read_back), format!("{:?}", events));
+    }
+
+    #[test]
+    fn huge_tag_length_is_rejected() {
+        let mut bytes = vec![3];
+        write_u64(&mut bytes, u64::MAX).unwrap();
+        bytes.extend_from_slice(b"sort");
+        let error = RayonEvent::read(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
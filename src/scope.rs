@@ -1,6 +1,6 @@
 //! Logging scope and Scope.
 use crate::raw_events::{now, RayonEvent, TaskId};
-use crate::{pool::log, pool::next_task_id};
+use crate::{pool::log, pool::next_task_id, pool::start_forked_task};
 use std::mem::transmute;
 
 ///Represents a fork-join scope which can be used to spawn any number of tasks. See [`scope()`] for more information.
@@ -73,8 +73,9 @@ impl<'scope> Scope<'scope> {
         // sorry I need to erase the borrow's lifetime.
         // it's ok though since the pointed self will survive all spawned tasks.
         let floating_self: &'scope Scope<'scope> = unsafe { transmute(self) };
+        let spawning_thread = rayon::current_thread_index();
         let logged_body = move |_: &rayon::Scope<'scope>| {
-            log(RayonEvent::TaskStart(spawned_id, now()));
+            start_forked_task(spawned_id, rayon::current_thread_index() != spawning_thread);
             body(floating_self);
             logs!(
                 RayonEvent::Child(floating_self.continuing_task_id),
@@ -144,8 +145,9 @@ impl<'scope> ScopeFifo<'scope> {
         // sorry I need to erase the borrow's lifetime.
         // it's ok though since the pointed self will survive all spawned tasks.
         let floating_self: &'scope ScopeFifo<'scope> = unsafe { transmute(self) };
+        let spawning_thread = rayon::current_thread_index();
         let logged_body = move |_: &rayon::ScopeFifo<'scope>| {
-            log(RayonEvent::TaskStart(spawned_id, now()));
+            start_forked_task(spawned_id, rayon::current_thread_index() != spawning_thread);
             body(floating_self);
             logs!(
                 RayonEvent::Child(floating_self.continuing_task_id),
@@ -0,0 +1,309 @@
+//! Offline scheduling simulator.
+//! We replay the tasks graph of a recorded log on a different number of threads
+//! and generate the corresponding synthetic log.
+//! Tasks keep their recorded durations: we do not model cache effects nor scheduling overheads
+//! (besides steal attempts in the work stealing policy).
+use crate::log::{idle_gaps, IdleKind, IdlePeriod, RunLog, StealLog, TaskLog};
+use crate::raw_events::{TaskId, TimeStamp};
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// How simulated threads pick their tasks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SchedulingPolicy {
+    /// Greedy list scheduling: a central queue of ready tasks (by readiness order)
+    /// and no thread stays idle while some task is ready.
+    Greedy,
+    /// Randomized work stealing, rayon style.
+    /// Each thread executes its tasks depth first from its own deque while idle threads
+    /// steal the oldest task from random victims.
+    /// Only successful steals are recorded in the simulated log.
+    WorkStealing {
+        /// seed for choosing victims (simulations are deterministic)
+        seed: u64,
+        /// time (in ns, at least 1) taken by each steal attempt
+        steal_duration: TimeStamp,
+    },
+}
+
+/// All information on the simulated execution.
+struct Simulation<'a> {
+    log: &'a RunLog,
+    /// how many fathers are not completed yet for each task
+    missing_fathers: Vec<usize>,
+    /// what we simulate
+    tasks: Vec<TaskLog>,
+    steals: Vec<StealLog>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(log: &'a RunLog) -> Self {
+        let mut missing_fathers = vec![0; log.tasks_logs.len()];
+        for task in &log.tasks_logs {
+            for &child in &task.children {
+                missing_fathers[child] += 1;
+            }
+        }
+        let tasks = log
+            .tasks_logs
+            .iter()
+            .map(|t| TaskLog {
+                start_time: 0,
+                end_time: 0,
+                thread_id: 0,
+                children: t.children.clone(),
+            })
+            .collect();
+        Simulation {
+            log,
+            missing_fathers,
+            tasks,
+            steals: Vec::new(),
+        }
+    }
+
+    /// Initially ready tasks, by recorded starting time.
+    fn roots(&self) -> Vec<TaskId> {
+        let mut roots: Vec<TaskId> = (0..self.tasks.len())
+            .filter(|&t| self.missing_fathers[t] == 0)
+            .collect();
+        roots.sort_by_key(|&t| (self.log.tasks_logs[t].start_time, t));
+        roots
+    }
+
+    /// Run given task on given thread, returning its ending time.
+    fn run(&mut self, task: TaskId, thread: usize, time: TimeStamp) -> TimeStamp {
+        let end_time = time + self.log.tasks_logs[task].duration();
+        let simulated = &mut self.tasks[task];
+        simulated.start_time = time;
+        simulated.end_time = end_time;
+        simulated.thread_id = thread;
+        end_time
+    }
+
+    /// Given task completed: return all children which become ready.
+    fn complete(&mut self, task: TaskId) -> Vec<TaskId> {
+        let missing_fathers = &mut self.missing_fathers;
+        self.log.tasks_logs[task]
+            .children
+            .iter()
+            .cloned()
+            .filter(|&child| {
+                missing_fathers[child] -= 1;
+                missing_fathers[child] == 0
+            })
+            .collect()
+    }
+
+    fn greedy(&mut self, threads_number: usize) {
+        let mut ready: VecDeque<TaskId> = self.roots().into_iter().collect();
+        // lowest indices first
+        let mut idle_threads: Vec<usize> = (0..threads_number).rev().collect();
+        let mut running = BinaryHeap::new();
+        let mut time = 0;
+        loop {
+            while !ready.is_empty() && !idle_threads.is_empty() {
+                let task = ready.pop_front().unwrap();
+                let thread = idle_threads.pop().unwrap();
+                let end_time = self.run(task, thread, time);
+                running.push(Reverse((end_time, thread, task)));
+            }
+            let (end_time, thread, task) = match running.pop() {
+                Some(Reverse(event)) => event,
+                None => break,
+            };
+            time = end_time;
+            ready.extend(self.complete(task));
+            idle_threads.push(thread);
+            idle_threads.sort_by(|a, b| b.cmp(a));
+        }
+    }
+
+    fn work_stealing(&mut self, threads_number: usize, seed: u64, steal_duration: TimeStamp) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut deques: Vec<VecDeque<TaskId>> = vec![VecDeque::new(); threads_number];
+        // events are (time, thread, completed task if any, else it is a steal attempt)
+        let mut events = BinaryHeap::new();
+        let mut remaining_tasks = self.tasks.len();
+        let mut running_tasks = 0;
+        let mut roots = self.roots().into_iter();
+        // first thread starts with the first root, others are in its deque
+        if let Some(first_root) = roots.next() {
+            let end_time = self.run(first_root, 0, 0);
+            running_tasks += 1;
+            events.push(Reverse((end_time, 0, Some(first_root))));
+            deques[0].extend(roots);
+        }
+        for thread in 1..threads_number {
+            events.push(Reverse((0, thread, None)));
+        }
+        while let Some(Reverse((time, thread, completed_task))) = events.pop() {
+            if remaining_tasks == 0 {
+                break;
+            }
+            let next_task = if let Some(task) = completed_task {
+                remaining_tasks -= 1;
+                running_tasks -= 1;
+                // we go on with the first child and offer the others to thieves
+                let mut children = self.complete(task).into_iter();
+                let first_child = children.next();
+                deques[thread].extend(children.rev());
+                first_child.or_else(|| deques[thread].pop_back())
+            } else {
+                let victim = (thread + rng.gen_range(1, threads_number)) % threads_number;
+                let stolen_task = deques[victim].pop_front();
+                if let Some(task) = stolen_task {
+                    self.steals.push(StealLog {
+                        time,
+                        thief: thread,
+                        victim: Some(victim),
+                        task: Some(task),
+                        attempts: 1,
+                    });
+                }
+                stolen_task
+            };
+            if let Some(task) = next_task {
+                let start_time = if completed_task.is_some() {
+                    time
+                } else {
+                    time + steal_duration
+                };
+                let end_time = self.run(task, thread, start_time);
+                running_tasks += 1;
+                events.push(Reverse((end_time, thread, Some(task))));
+            } else if running_tasks == 0 && deques.iter().all(|d| d.is_empty()) {
+                // nothing will ever become ready (only possible on invalid logs or with one thread)
+                break;
+            } else {
+                events.push(Reverse((time + steal_duration, thread, None)));
+            }
+        }
+    }
+}
+
+/// Simulate the execution of given log's tasks graph on given number of threads.
+pub(crate) fn simulate(log: &RunLog, threads_number: usize, policy: SchedulingPolicy) -> RunLog {
+    assert!(threads_number > 0, "we need at least one thread");
+    let mut simulation = Simulation::new(log);
+    match policy {
+        SchedulingPolicy::Greedy => simulation.greedy(threads_number),
+        SchedulingPolicy::WorkStealing {
+            seed,
+            steal_duration,
+        } => simulation.work_stealing(threads_number, seed, steal_duration.max(1)),
+    }
+    let tasks = simulation.tasks;
+    let duration = tasks.iter().map(|t| t.end_time).max().unwrap_or(0);
+    // simulated idle threads never sleep
+    let idle_periods = if tasks.is_empty() {
+        Vec::new()
+    } else {
+        idle_gaps(&tasks, threads_number)
+            .into_iter()
+            .map(|(thread_id, start, end)| IdlePeriod {
+                thread_id,
+                start,
+                end,
+                kind: IdleKind::Searching,
+            })
+            .sorted_by_key(|p| (p.start, p.thread_id))
+            .collect()
+    };
+    let mut metadata = log.metadata.clone();
+    metadata
+        .user
+        .insert("simulated policy".to_string(), format!("{:?}", policy));
+    RunLog {
+        threads_number,
+        tasks_logs: tasks,
+        duration,
+        tags: log.tags.clone(),
+        subgraphs: log.subgraphs.clone(),
+        subgraphs_parents: log.subgraphs_parents.clone(),
+        steals: simulation.steals,
+        idle_periods,
+        partial: log.partial,
+        metadata,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Task 0 forks tasks 1 (90ns) and 2 (190ns), joined by task 3.
+    fn fork_join_log() -> RunLog {
+        RunLog::from_tasks(
+            2,
+            &[
+                (0, 10, 0, &[1, 2]),
+                (10, 100, 0, &[3]),
+                (10, 200, 1, &[3]),
+                (200, 210, 0, &[]),
+            ],
+        )
+    }
+
+    fn schedule(log: &RunLog) -> Vec<(TimeStamp, TimeStamp, usize)> {
+        log.tasks_logs
+            .iter()
+            .map(|t| (t.start_time, t.end_time, t.thread_id))
+            .collect()
+    }
+
+    #[test]
+    fn greedy_schedules() {
+        let log = fork_join_log();
+        let sequential = simulate(&log, 1, SchedulingPolicy::Greedy);
+        assert_eq!(sequential.duration, 300);
+        assert!(sequential.tasks_logs.iter().all(|t| t.thread_id == 0));
+        assert!(sequential.idle_periods.is_empty());
+        let parallel = simulate(&log, 2, SchedulingPolicy::Greedy);
+        assert_eq!(
+            schedule(&parallel),
+            vec![(0, 10, 0), (10, 100, 0), (10, 200, 1), (200, 210, 0)]
+        );
+        assert_eq!(parallel.validate(), Vec::new());
+        let idle: Vec<_> = parallel
+            .idle_periods
+            .iter()
+            .map(|p| (p.thread_id, p.start, p.end))
+            .collect();
+        assert_eq!(idle, vec![(1, 0, 10), (0, 100, 200), (1, 200, 210)]);
+        // more threads than parallelism changes nothing
+        assert_eq!(
+            simulate(&log, 8, SchedulingPolicy::Greedy).duration,
+            parallel.duration
+        );
+    }
+
+    #[test]
+    fn work_stealing_schedules() {
+        let log = fork_join_log();
+        let policy = SchedulingPolicy::WorkStealing {
+            seed: 0,
+            steal_duration: 5,
+        };
+        let simulated = simulate(&log, 2, policy);
+        // thread 1 steals task 2 when thread 0 forks it and pays the steal duration
+        assert_eq!(
+            schedule(&simulated),
+            vec![(0, 10, 0), (10, 100, 0), (15, 205, 1), (205, 215, 1)]
+        );
+        assert_eq!(simulated.duration, 215);
+        assert_eq!(simulated.steals.len(), 1);
+        let steal = &simulated.steals[0];
+        assert_eq!(
+            (steal.time, steal.thief, steal.victim, steal.task),
+            (10, 1, Some(0), Some(2))
+        );
+        assert_eq!(simulated.validate(), Vec::new());
+        // simulations are deterministic
+        assert_eq!(simulate(&log, 2, policy), simulated);
+        assert_eq!(simulate(&log, 1, policy).duration, 300);
+    }
+}
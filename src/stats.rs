@@ -1,8 +1,37 @@
 //! `LoggedPool` structure for logging raw tasks events.
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 
 // use crate::fork_join_graph::{create_graph, Block};
-use crate::log::RunLog;
+use crate::log::{IdleKind, RunLog, WorkSpan};
+
+/// Number of resamplings for bootstrap confidence intervals.
+const BOOTSTRAP_RESAMPLES: usize = 1000;
+/// Confidence level of all intervals.
+const CONFIDENCE: f64 = 0.95;
+
+/// Statistics on the durations of all runs of an algorithm.
+/// Intervals are 95% percentile bootstrap confidence intervals: we resample the runs durations
+/// (with replacement, same number of runs) 1000 times and keep the 2.5th and 97.5th
+/// percentiles of the obtained statistics.
+/// Resampling is seeded so reports are reproducible.
+/// With less than two runs intervals are reduced to the value itself.
+/// All values are in nanoseconds.
+pub struct DurationsSummary {
+    /// mean duration of all runs
+    pub mean: f64,
+    /// 95% bootstrap confidence interval (lower, upper) of the mean
+    pub mean_interval: (f64, f64),
+    /// median duration of all runs (mean of the two middle ones for even numbers of runs)
+    pub median: f64,
+    /// 95% bootstrap confidence interval (lower, upper) of the median
+    pub median_interval: (f64, f64),
+    /// standard deviation
+    pub std_dev: f64,
+    /// median absolute deviation
+    pub mad: f64,
+}
 
 /// This struct mainly supplies the methods that can be used to get various statistics.
 pub struct Stats<'a> {
@@ -150,6 +179,35 @@ impl<'l> Stats<'l> {
             .map(move |(duration, activity)| duration * self.threads_number as u64 - activity)
     }
 
+    /// This returns the time spent doing given idle activity, averaged across all runs,
+    /// for all experiments.
+    pub fn idle_kind_times<'a, 'b: 'a>(&'b self, kind: IdleKind) -> impl Iterator<Item = u64> + 'a {
+        self.logs.iter().map(move |algorithm| {
+            algorithm.iter().map(|run| run.idle_time(kind)).sum::<u64>() / self.runs_number as u64
+        })
+    }
+
+    /// This returns the work-span analysis averaged across all runs for all experiments.
+    pub fn work_spans<'a, 'b: 'a>(&'b self) -> impl Iterator<Item = WorkSpan> + 'a {
+        self.logs.iter().map(move |algorithm| {
+            let runs_number = self.runs_number as u64;
+            let analysis: Vec<WorkSpan> = algorithm.iter().map(|run| run.work_span()).collect();
+            WorkSpan {
+                work: analysis.iter().map(|a| a.work).sum::<u64>() / runs_number,
+                span: analysis.iter().map(|a| a.span).sum::<u64>() / runs_number,
+                duration: analysis.iter().map(|a| a.duration).sum::<u64>() / runs_number,
+                threads_number: self.threads_number,
+            }
+        })
+    }
+
+    /// This returns the work-span analysis of the median run for all experiments.
+    pub fn work_spans_median<'a, 'b: 'a>(&'b self) -> impl Iterator<Item = WorkSpan> + 'a {
+        self.logs
+            .iter()
+            .map(move |algorithm| algorithm[self.runs_number / 2].work_span())
+    }
+
     /// This returns the total time for the median runs for all experiments.
     pub fn total_times_median<'a, 'b: 'a>(&'b self) -> impl Iterator<Item = u64> + 'a {
         self.logs
@@ -173,4 +231,233 @@ impl<'l> Stats<'l> {
                 (total_time * self.threads_number as u64) - compute_time
             })
     }
+
+    /// This returns the time spent doing given idle activity for the median run
+    /// for all experiments.
+    pub fn idle_kind_times_median<'a, 'b: 'a>(
+        &'b self,
+        kind: IdleKind,
+    ) -> impl Iterator<Item = u64> + 'a {
+        self.logs
+            .iter()
+            .map(move |algorithm| algorithm[self.runs_number / 2].idle_time(kind))
+    }
+
+    /// This returns the durations of all runs, for all experiments.
+    fn durations<'a, 'b: 'a>(&'b self) -> impl Iterator<Item = Vec<u64>> + 'a {
+        self.logs
+            .iter()
+            .map(|algorithm| algorithm.iter().map(|run| run.duration).collect())
+    }
+
+    /// This returns the dispersion of durations and the confidence intervals
+    /// of their mean and median, for all experiments.
+    pub fn durations_summaries<'a, 'b: 'a>(
+        &'b self,
+    ) -> impl Iterator<Item = DurationsSummary> + 'a {
+        self.durations().map(|durations| {
+            let durations: Vec<f64> = durations.into_iter().map(|d| d as f64).collect();
+            DurationsSummary {
+                mean: mean(&durations),
+                mean_interval: bootstrap_interval(&durations, mean),
+                median: median(&durations),
+                median_interval: bootstrap_interval(&durations, median),
+                std_dev: std_dev(&durations),
+                mad: mad(&durations),
+            }
+        })
+    }
+
+    /// This returns for each pair of experiments the p-value of a Mann-Whitney U test
+    /// on their durations.
+    /// Small values mean one algorithm tends to be faster than the other.
+    pub fn pairwise_p_values(&self) -> Vec<Vec<f64>> {
+        let durations: Vec<Vec<u64>> = self.durations().collect();
+        durations
+            .iter()
+            .map(|first| {
+                durations
+                    .iter()
+                    .map(|second| mann_whitney_u(first, second))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn mean(sample: &[f64]) -> f64 {
+    if sample.is_empty() {
+        0.0
+    } else {
+        sample.iter().sum::<f64>() / sample.len() as f64
+    }
+}
+
+pub(crate) fn median(sample: &[f64]) -> f64 {
+    if sample.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = sample.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    // both indices are the same for odd lengths
+    (sorted[(sorted.len() - 1) / 2] + sorted[sorted.len() / 2]) / 2.0
+}
+
+/// Sample standard deviation.
+fn std_dev(sample: &[f64]) -> f64 {
+    if sample.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(sample);
+    let squares: f64 = sample.iter().map(|x| (x - mean) * (x - mean)).sum();
+    (squares / (sample.len() - 1) as f64).sqrt()
+}
+
+/// Median absolute deviation.
+fn mad(sample: &[f64]) -> f64 {
+    let median = median(sample);
+    let deviations: Vec<f64> = sample.iter().map(|x| (x - median).abs()).collect();
+    self::median(&deviations)
+}
+
+/// Percentile bootstrap confidence interval of given statistic.
+/// Resamplings are seeded so that reports are reproducible.
+fn bootstrap_interval<F: Fn(&[f64]) -> f64>(sample: &[f64], statistic: F) -> (f64, f64) {
+    if sample.len() < 2 {
+        let value = statistic(sample);
+        return (value, value);
+    }
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut resample = vec![0.0; sample.len()];
+    let mut values: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            for x in resample.iter_mut() {
+                *x = sample[rng.gen_range(0, sample.len())];
+            }
+            statistic(&resample)
+        })
+        .collect();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let tail = (1.0 - CONFIDENCE) / 2.0;
+    let last = (BOOTSTRAP_RESAMPLES - 1) as f64;
+    (
+        values[(tail * last).round() as usize],
+        values[((1.0 - tail) * last).round() as usize],
+    )
+}
+
+/// Two-sided p-value of the Mann-Whitney U test between given samples.
+/// We use the normal approximation (with ties and continuity corrections)
+/// which is accurate enough for a dozen runs or more.
+pub(crate) fn mann_whitney_u(first: &[u64], second: &[u64]) -> f64 {
+    let (n1, n2) = (first.len() as f64, second.len() as f64);
+    if first.is_empty() || second.is_empty() {
+        return 1.0;
+    }
+    let mut values: Vec<(u64, bool)> = first
+        .iter()
+        .map(|&d| (d, true))
+        .chain(second.iter().map(|&d| (d, false)))
+        .collect();
+    values.sort_unstable();
+    // sum the ranks of the first sample, averaging ranks of ties
+    let mut first_ranks = 0.0;
+    let mut ties_correction = 0.0;
+    let mut start = 0;
+    while start < values.len() {
+        let end = start
+            + values[start..]
+                .iter()
+                .take_while(|(d, _)| *d == values[start].0)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let ties = (end - start) as f64;
+        first_ranks += rank * values[start..end].iter().filter(|(_, f)| *f).count() as f64;
+        ties_correction += ties * ties * ties - ties;
+        start = end;
+    }
+    let u = first_ranks - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties_correction / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let deviation = ((u - n1 * n2 / 2.0).abs() - 0.5).max(0.0);
+    let z = deviation / variance.sqrt();
+    (2.0 * (1.0 - normal_cdf(z))).min(1.0)
+}
+
+/// Cumulative distribution function of the standard normal distribution.
+fn normal_cdf(x: f64) -> f64 {
+    (1.0 + erf(x / std::f64::consts::SQRT_2)) / 2.0
+}
+
+/// Error function (Abramowitz and Stegun 7.1.26, error below 1.5e-7).
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let y = 1.0 - polynomial * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+/// Return the significance marker for given p-value.
+pub(crate) fn significance_marker(p_value: f64) -> &'static str {
+    match p_value {
+        p if p < 0.001 => "***",
+        p if p < 0.01 => "**",
+        p if p < 0.05 => "*",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispersion() {
+        let sample = [1.0, 2.0, 4.0, 7.0];
+        assert_eq!(mean(&sample), 3.5);
+        assert_eq!(median(&sample), 3.0);
+        assert_eq!(median(&sample[..3]), 2.0);
+        assert_eq!(mad(&sample), 1.5);
+        assert!((std_dev(&sample) - 7.0f64.sqrt()).abs() < 1e-12);
+        assert_eq!(std_dev(&sample[..1]), 0.0);
+    }
+
+    #[test]
+    fn bootstrap_intervals() {
+        let sample: Vec<f64> = (1..=20).map(f64::from).collect();
+        let (lower, upper) = bootstrap_interval(&sample, mean);
+        // roughly two standard errors around the mean (10.5)
+        assert!((7.5..9.5).contains(&lower) && (11.5..13.5).contains(&upper));
+        // resampling is seeded
+        assert_eq!(bootstrap_interval(&sample, mean), (lower, upper));
+        assert_eq!(bootstrap_interval(&[3.0; 10], median), (3.0, 3.0));
+        assert_eq!(bootstrap_interval(&[3.0], mean), (3.0, 3.0));
+    }
+
+    #[test]
+    fn mann_whitney() {
+        let fast = [1, 2, 3, 4, 5];
+        let slow = [6, 7, 8, 9, 10];
+        // U = 0, z = (12.5 - 0.5) / sqrt(25 * 11 / 12)
+        let p_value = mann_whitney_u(&fast, &slow);
+        assert!((p_value - 0.012_19).abs() < 1e-4, "p-value is {}", p_value);
+        assert_eq!(mann_whitney_u(&slow, &fast), p_value);
+        assert_eq!(significance_marker(p_value), "*");
+        assert!(mann_whitney_u(&fast, &fast) > 0.999);
+        // only ties: no information
+        assert_eq!(mann_whitney_u(&[5, 5, 5], &[5, 5]), 1.0);
+        assert_eq!(mann_whitney_u(&[], &slow), 1.0);
+        let interleaved = mann_whitney_u(&[1, 3, 5, 7, 9], &[2, 4, 6, 8, 10]);
+        assert!(interleaved > 0.5);
+    }
 }
@@ -2,11 +2,28 @@
 //! every thread has its own storage and will be the only one to write in it.
 //! however after computations end, a master thread will extract all elements
 //! from all storages. it thus requires an `UnsafeCell`.
+//!
+//! A storage can also stream its full blocks to a background writer thread
+//! which appends them to a file. This way memory usage stays bounded on long runs.
 use std::cell::UnsafeCell;
 use std::collections::LinkedList;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
 
 const BLOCK_SIZE: usize = 10_000;
 
+/// Elements which can be streamed to disk and read back.
+pub(crate) trait Record: Sized {
+    /// Write ourselves in given writer.
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    /// Read next element from given reader. Returns `None` on end of file.
+    fn read<R: Read>(reader: &mut R) -> io::Result<Option<Self>>;
+}
+
 /// We store elements in a list of blocks.
 /// Each `Block` is a contiguous memory block.
 struct Block<T> {
@@ -38,16 +55,87 @@ impl<T> Block<T> {
         self.data.len() == BLOCK_SIZE
     }
 
+    /// Is there anything inside.
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Remove all elements but keep the memory.
+    fn clear(&mut self) {
+        self.data.clear()
+    }
+
     /// Iterator on all elements.
     fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> + 'a {
         self.data.iter()
     }
 }
 
+/// Messages sent to the background writer.
+enum SinkMessage<T> {
+    /// Append this block to the file.
+    Block(Block<T>),
+    /// Truncate the file.
+    Clear,
+    /// Answer back when everything before is on disk.
+    Flush(Sender<io::Result<()>>),
+}
+
+/// Where a streaming storage sends its blocks.
+struct Sink<T> {
+    sender: Sender<SinkMessage<T>>,
+    path: PathBuf,
+}
+
+/// Background writer's loop: write all received blocks to the file at given path.
+/// We keep the first error encountered and report it on next flush.
+fn write_blocks<T: Record>(path: PathBuf, receiver: Receiver<SinkMessage<T>>) {
+    let mut file = File::create(&path).map(BufWriter::new);
+    let mut error: Option<io::Error> = None;
+    for message in receiver {
+        match message {
+            SinkMessage::Block(block) => {
+                if let Ok(ref mut writer) = file {
+                    if let Err(e) = block.iter().try_for_each(|e| e.write(writer)) {
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+            SinkMessage::Clear => {
+                file = File::create(&path).map(BufWriter::new);
+                error = None;
+            }
+            SinkMessage::Flush(answer) => {
+                let status = match (&mut file, error.take()) {
+                    (Err(e), _) => Err(io::Error::new(e.kind(), e.to_string())),
+                    (Ok(_), Some(e)) => Err(e),
+                    (Ok(writer), None) => writer.flush(),
+                };
+                answer.send(status).ok();
+            }
+        }
+    }
+}
+
+/// Read back all elements streamed to the file at given path.
+pub(crate) fn read_records<T: Record, P: AsRef<Path>>(path: P) -> io::Result<Vec<T>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut elements = Vec::new();
+    while let Some(element) = T::read(&mut reader)? {
+        elements.push(element)
+    }
+    Ok(elements)
+}
+
 /// Fast structure (worst case O(1)) for pushing
 /// logs in a thread.
 pub(crate) struct Storage<T> {
     data: UnsafeCell<LinkedList<Block<T>>>,
+    sink: Option<Sink<T>>,
+    /// maximal number of blocks kept (oldest ones get overwritten)
+    max_blocks: Option<usize>,
+    /// did we overwrite anything since last clear
+    truncated: AtomicBool,
 }
 
 unsafe impl<T: Sync> Sync for Storage<T> {}
@@ -66,15 +154,38 @@ impl<T> Storage<T> {
         list.push_front(first_block);
         Storage {
             data: UnsafeCell::new(list),
+            sink: None,
+            max_blocks: None,
+            truncated: AtomicBool::new(false),
+        }
+    }
+
+    /// Create a new storage space keeping only the last (around) `capacity` elements.
+    /// Oldest elements get overwritten.
+    pub fn bounded(capacity: usize) -> Self {
+        let blocks_number = capacity / BLOCK_SIZE + (capacity % BLOCK_SIZE != 0) as usize;
+        Storage {
+            // we always keep the block we are filling plus the full ones
+            max_blocks: Some(blocks_number.max(1) + 1),
+            ..Storage::new()
         }
     }
 
+    /// Did we lose our oldest elements.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
     /// Destroy all elements (frees all block memory).
     pub fn clear(&self) {
         let list = unsafe { self.data.get().as_mut() }.unwrap();
         list.clear();
         let first_block = Block::new();
         list.push_front(first_block);
+        self.truncated.store(false, Ordering::Relaxed);
+        if let Some(ref sink) = self.sink {
+            sink.sender.send(SinkMessage::Clear).ok();
+        }
     }
 
     /// Add given element to storage space.
@@ -82,10 +193,65 @@ impl<T> Storage<T> {
         let list = unsafe { self.data.get().as_mut() }.unwrap();
         let space_needed = list.front().unwrap().is_full();
         if space_needed {
-            list.push_front(Block::new());
+            if let Some(ref sink) = self.sink {
+                // the block is handed to the writer so we keep only one block in memory
+                let full_block = list.pop_front().unwrap();
+                sink.sender.send(SinkMessage::Block(full_block)).ok();
+            }
+            if self.max_blocks == Some(list.len()) {
+                // recycle the oldest block
+                let mut oldest_block = list.pop_back().unwrap();
+                oldest_block.clear();
+                list.push_front(oldest_block);
+                self.truncated.store(true, Ordering::Relaxed);
+            } else {
+                list.push_front(Block::new());
+            }
         }
         list.front_mut().unwrap().push(element)
     }
+
+    /// Return the file we stream to (if any).
+    pub fn sink_path(&self) -> Option<&Path> {
+        self.sink.as_ref().map(|s| s.path.as_path())
+    }
+
+    /// Send all elements still in memory to the writer and wait until they are on disk.
+    /// Does nothing if we are not streaming.
+    pub fn flush(&self) -> io::Result<()> {
+        if let Some(ref sink) = self.sink {
+            let list = unsafe { self.data.get().as_mut() }.unwrap();
+            while let Some(block) = list.pop_back() {
+                if !block.is_empty() {
+                    sink.sender.send(SinkMessage::Block(block)).ok();
+                }
+            }
+            list.push_front(Block::new());
+            let (answer_sender, answer) = channel();
+            sink.sender
+                .send(SinkMessage::Flush(answer_sender))
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "logs writer is dead"))?;
+            answer
+                .recv()
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "logs writer is dead"))??;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Record + Send + 'static> Storage<T> {
+    /// Create a new storage space streaming its full blocks to the file at given path.
+    /// The file is written by a background thread.
+    pub fn streaming_to<P: AsRef<Path>>(path: P) -> Self {
+        let (sender, receiver) = channel();
+        let path = path.as_ref().to_path_buf();
+        let writer_path = path.clone();
+        thread::spawn(move || write_blocks(writer_path, receiver));
+        Storage {
+            sink: Some(Sink { sender, path }),
+            ..Storage::new()
+        }
+    }
 }
 
 impl<'a, T: 'a> Storage<T> {
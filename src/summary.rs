@@ -0,0 +1,146 @@
+//! Text summary of logs, for terminals.
+use crate::compare::time_string;
+use crate::log::{IdleKind, RunLog};
+use itertools::Itertools;
+use std::fmt::{self, Write};
+
+/// Characters for increasing activity levels.
+const UNICODE_LEVELS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII_LEVELS: [char; 9] = [' ', '.', ':', '-', '=', '+', '*', '#', '@'];
+
+/// Return for given thread the fraction of time spent running tasks in each of `cells`
+/// equal slices of the run (which starts at given time).
+fn activity(log: &RunLog, thread: usize, start: u64, cells: usize) -> Vec<f64> {
+    let cell_duration = log.duration.max(1) as f64 / cells as f64;
+    let mut activity = vec![0.0; cells];
+    for task in log.tasks_logs.iter().filter(|t| t.thread_id == thread) {
+        let (task_start, task_end) = (
+            (task.start_time - start) as f64,
+            (task.end_time - start) as f64,
+        );
+        let first_cell = (task_start / cell_duration) as usize;
+        let last_cell = ((task_end / cell_duration) as usize).min(cells - 1);
+        for (cell, cell_activity) in activity
+            .iter_mut()
+            .enumerate()
+            .take(last_cell + 1)
+            .skip(first_cell)
+        {
+            let cell_start = cell as f64 * cell_duration;
+            let overlap = task_end.min(cell_start + cell_duration) - task_start.max(cell_start);
+            *cell_activity += overlap.max(0.0) / cell_duration;
+        }
+    }
+    activity
+}
+
+/// Write a summary of given log, `width` characters wide.
+pub(crate) fn write_summary<W: Write>(
+    log: &RunLog,
+    width: usize,
+    ascii: bool,
+    writer: &mut W,
+) -> fmt::Result {
+    let levels = if ascii { ASCII_LEVELS } else { UNICODE_LEVELS };
+    let (vertical, horizontal) = if ascii { ('|', '-') } else { ('│', '─') };
+    let start = log
+        .tasks_logs
+        .iter()
+        .map(|t| t.start_time)
+        .min()
+        .unwrap_or(0);
+    writeln!(
+        writer,
+        "{} tasks on {} threads in {}{}",
+        log.tasks_logs.len(),
+        log.threads_number,
+        time_string(log.duration),
+        if log.partial { " (partial log)" } else { "" }
+    )?;
+
+    // one activity line per thread
+    let label_width = format!("{}", log.threads_number.saturating_sub(1)).len();
+    let cells = width.saturating_sub(label_width + 10).max(10);
+    writeln!(
+        writer,
+        "{}",
+        horizontal.to_string().repeat(cells + label_width + 10)
+    )?;
+    for thread in 0..log.threads_number {
+        let activity = activity(log, thread, start, cells);
+        let line: String = activity
+            .iter()
+            .map(|a| levels[(a.min(1.0) * 8.0).round() as usize])
+            .collect();
+        let utilisation = activity.iter().sum::<f64>() / cells as f64 * 100.0;
+        writeln!(
+            writer,
+            "{:>w$} {}{}{} {:5.1}%",
+            thread,
+            vertical,
+            line,
+            vertical,
+            utilisation,
+            w = label_width
+        )?;
+    }
+    writeln!(
+        writer,
+        "{}",
+        horizontal.to_string().repeat(cells + label_width + 10)
+    )?;
+
+    let work_span = log.work_span();
+    writeln!(
+        writer,
+        "work: {}, critical path: {}\nparallelism: {:.2}, speedup: {:.2}, efficiency: {:.1}%",
+        time_string(work_span.work),
+        time_string(work_span.span),
+        work_span.parallelism(),
+        work_span.speedup(),
+        work_span.efficiency() * 100.0
+    )?;
+    writeln!(
+        writer,
+        "steals: {} successful, {} failed\nidle: {} searching, {} sleeping",
+        log.successful_steals(),
+        log.failed_steals(),
+        time_string(log.idle_time(IdleKind::Searching)),
+        time_string(log.idle_time(IdleKind::Sleeping)),
+    )?;
+
+    let stats = log.stats();
+    if !stats.is_empty() {
+        let tag_width = stats
+            .keys()
+            .map(|t| t.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(3);
+        writeln!(
+            writer,
+            "{:<w$} {:>12} {:>10} {:>12}",
+            "tag",
+            "work amount",
+            "time",
+            "per unit",
+            w = tag_width
+        )?;
+        for (tag, (work, duration)) in stats.iter().sorted() {
+            writeln!(
+                writer,
+                "{:<w$} {:>12} {:>10} {:>12}",
+                tag,
+                work,
+                time_string(*duration),
+                time_string(if *work == 0 {
+                    0
+                } else {
+                    duration / *work as u64
+                }),
+                w = tag_width
+            )?;
+        }
+    }
+    Ok(())
+}
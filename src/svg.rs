@@ -1,6 +1,9 @@
 //! Small module with display related functions.
 
+use crate::compare::time_string;
+use crate::fork_join_graph::Aggregation;
 use crate::log::RunLog;
+use crate::raw_events::{TaskId, TimeStamp};
 use itertools::Itertools;
 use std::cmp::max;
 use std::collections::HashMap;
@@ -18,17 +21,27 @@ pub const HISTOGRAM_COLORS: [&str; 7] = [
 ];
 
 pub(crate) type Point = (f64, f64);
+/// Detailed rectangles and segments of a summary.
+pub(crate) type Details = (Vec<Rectangle>, Vec<(Point, Point)>);
 
 /// all graphics elements for one `RunLog` display.
 pub struct Scene {
     /// Each task is an animated rectangle.
     /// We also display a black rectangle underneath.
     /// idle times are also displayed as animated rectangles.
+    /// Tasks rectangles know their task (see `Rectangle::task`).
     pub rectangles: Vec<Rectangle>,
     /// Dependencies are shown as segments.
     pub segments: Vec<(Point, Point)>,
+    /// Some dependencies are shown as arrows, on top of tasks.
+    pub arrows: Vec<(Point, Point)>,
     /// All available tags
     pub tags: Vec<String>,
+    /// Number of threads (for the legend).
+    pub threads_number: usize,
+    /// Where the time axis starts (in scene coordinates, one unit being one nano second)
+    /// and the first and last times it displays.
+    pub time_axis: Option<(Point, TimeStamp, TimeStamp)>,
 }
 
 impl Scene {
@@ -36,9 +49,13 @@ impl Scene {
         Scene {
             rectangles: Vec::new(),
             segments: Vec::new(),
+            arrows: Vec::new(),
             tags: once("_NO_TAGS_".to_string())
+                .chain(once("_CRITICAL_PATH_".to_string()))
                 .chain(logs.tags.iter().cloned())
                 .collect(),
+            threads_number: logs.threads_number,
+            time_axis: None,
         }
     }
 }
@@ -71,6 +88,10 @@ pub struct Rectangle {
     pub animation: (u64, u64),
     /// to each tag its label and opacity
     pub information: HashMap<String, (String, f64)>,
+    /// displayed task, if any
+    pub task: Option<TaskId>,
+    /// for summaries of collapsed blocks, the detailed rectangles and segments
+    pub details: Option<Details>,
 }
 
 impl Rectangle {
@@ -90,21 +111,75 @@ impl Rectangle {
             height: sizes.1,
             animation,
             information,
+            task: None,
+            details: None,
         }
     }
 }
 
-/// saves a set of rectangles and edges as an animated svg file.
-/// 1 animated second is 1 milli second of run.
-pub(crate) fn write_svg_file<P: AsRef<Path>>(scene: &Scene, path: P) -> Result<(), Error> {
+/// How to render svg files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvgOptions {
+    /// Animate tasks (1 animated second is 1 milli second of run).
+    /// Static files display final states with a time axis.
+    /// They can be used in papers or opened by any image viewer.
+    /// In both cases tasks show their "_NO_TAGS_" colors, with their labels as tooltips:
+    /// svg files contain no script, use `RunLog::save_html` to switch between tags.
+    pub animated: bool,
+    /// Display a legend of threads colors.
+    pub legend: bool,
+    /// Collapse small blocks of the fork-join graph, which keeps huge logs' files small.
+    pub aggregation: Option<Aggregation>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            animated: true,
+            legend: false,
+            aggregation: None,
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Options for static files with a legend.
+    pub fn static_svg() -> Self {
+        SvgOptions {
+            animated: false,
+            legend: true,
+            aggregation: None,
+        }
+    }
+}
+
+/// Space (in svg units) below the scene for the time axis and the legend.
+const BOTTOM_MARGIN: u32 = 100;
+
+/// saves a set of rectangles and edges as an svg file.
+/// When animated, 1 animated second is 1 milli second of run.
+pub(crate) fn write_svg_file<P: AsRef<Path>>(
+    scene: &Scene,
+    path: P,
+    options: &SvgOptions,
+) -> Result<(), Error> {
     let mut file = File::create(path)?;
-    fill_svg_file(scene, &mut file)
+    fill_svg_file(scene, &mut file, options)
 }
 
-/// fill given file with a set of rectangles and edges as an animated svg.
-pub(crate) fn fill_svg_file(scene: &Scene, file: &mut File) -> Result<(), Error> {
+/// fill given file with a set of rectangles and edges as an svg.
+pub(crate) fn fill_svg_file(
+    scene: &Scene,
+    file: &mut File,
+    options: &SvgOptions,
+) -> Result<(), Error> {
     let svg_width: u32 = 1920; // this is just an aspect ratio
     let svg_height: u32 = 1080;
+    let view_height = if options.animated && !options.legend {
+        svg_height
+    } else {
+        svg_height + BOTTOM_MARGIN
+    };
 
     let xmax = scene
         .rectangles
@@ -141,7 +216,7 @@ pub(crate) fn fill_svg_file(scene: &Scene, file: &mut File) -> Result<(), Error>
         file,
         "<?xml version=\"1.0\"?>
 <svg viewBox=\"0 0 {} {}\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\">",
-        svg_width, svg_height,
+        svg_width, view_height,
     )?;
     // we start by edges so they will end up below tasks
     for (start, end) in &scene.segments {
@@ -180,129 +255,185 @@ pub(crate) fn fill_svg_file(scene: &Scene, file: &mut File) -> Result<(), Error>
         )?;
     }
 
-    for (tag_index, tag) in scene.tags.iter().enumerate() {
-        writeln!(file, "<g id=\"tasks_colors_{}_{}\">", random_id, tag)?;
-        for (index, rectangle) in scene.rectangles.iter().enumerate() {
-            if let Some((label, opacity)) = rectangle.information.get(tag) {
-                // now the animated one
+    if options.legend {
+        write_legend(scene, file, svg_height + BOTTOM_MARGIN / 2)?;
+    }
+
+    // tasks show their "_NO_TAGS_" colors and their labels as tooltips.
+    // use `RunLog::save_html` for switching between tags.
+    for rectangle in &scene.rectangles {
+        if let Some((label, opacity)) = rectangle.information.get("_NO_TAGS_") {
+            let (x, y, width, height) = (
+                (rectangle.x - xmin) * xscale,
+                (rectangle.y - ymin) * yscale,
+                rectangle.width * xscale,
+                rectangle.height * yscale,
+            );
+            let color = format!(
+                "rgba({},{},{},{})",
+                (rectangle.color[0] * 255.0) as u32,
+                (rectangle.color[1] * 255.0) as u32,
+                (rectangle.color[2] * 255.0) as u32,
+                opacity
+            );
+            if options.animated {
                 let (start_time, end_time) = rectangle.animation;
-                writeln!(file,
-            "<rect class=\"task{}\" id=\"{}_{}\" x=\"{}\" y=\"{}\" width=\"0\" height=\"{}\" fill=\"rgba({},{},{},{})\">
+                writeln!(
+                    file,
+                    "<rect x=\"{}\" y=\"{}\" width=\"0\" height=\"{}\" fill=\"{}\"><title>{}</title>
 <animate attributeType=\"XML\" attributeName=\"width\" from=\"0\" to=\"{}\" begin=\"{}ms\" dur=\"{}ms\" fill=\"freeze\"/>
 </rect>",
-        random_id,
-        index,
-        tag_index,
-        (rectangle.x-xmin)*xscale,
-        (rectangle.y-ymin)*yscale,
-        rectangle.height*yscale,
-        (rectangle.color[0] * 255.0) as u32,
-        (rectangle.color[1] * 255.0) as u32,
-        (rectangle.color[2] * 255.0) as u32,
-        opacity,
-        rectangle.width*xscale,
-        max(((start_time-min_time)*60_000) / total_time, 1),
-        max(((end_time - start_time)*60_000) / total_time, 1),
-        )?;
-
-                // the labels now
-                writeln!(file, "<g id=\"tip_{}_{}_{}\">", random_id, index, tag_index)?;
-                let x = svg_width - 400;
-                let height = label.lines().count() as u32 * 20;
-                let mut y = svg_height - height - 40;
+                    x,
+                    y,
+                    height,
+                    color,
+                    escape_xml(label),
+                    width,
+                    max(((start_time - min_time) * 60_000) / total_time, 1),
+                    max(((end_time - start_time) * 60_000) / total_time, 1),
+                )?;
+            } else {
                 writeln!(
+                    file,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{}</title></rect>",
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                    escape_xml(label)
+                )?;
+            }
+        }
+    }
+    write_arrows(scene, file, (xmin, ymin), (xscale, yscale), random_id)?;
+
+    if !options.animated {
+        if let Some(((x, y), start, end)) = scene.time_axis {
+            write_time_axis(
+                file,
+                ((x - xmin) * xscale, (y - ymin) * yscale),
+                end - start,
+                xscale,
+            )?;
+        }
+    }
+
+    write!(file, "</svg>")?;
+    Ok(())
+}
+
+/// Escape text for inclusion inside svg elements.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draw the scene's arrows, given the scene's origin and the scales.
+fn write_arrows(
+    scene: &Scene,
+    file: &mut File,
+    origin: Point,
+    scales: (f64, f64),
+    random_id: usize,
+) -> Result<(), Error> {
+    if scene.arrows.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        file,
+        "<defs><marker id=\"arrow_{}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\"/></marker></defs>",
+        random_id
+    )?;
+    for (start, end) in &scene.arrows {
+        writeln!(
             file,
-            "<rect x=\"{}\" y=\"{}\" width=\"300\" height=\"{}\" fill=\"white\" stroke=\"black\"/>",
-            x,
-            y,
-            height + 10
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"1.5\" marker-end=\"url(#arrow_{})\"/>",
+            (start.0 - origin.0) * scales.0,
+            (start.1 - origin.1) * scales.1,
+            (end.0 - origin.0) * scales.0,
+            (end.1 - origin.1) * scales.1,
+            random_id
         )?;
-                for line in label.lines() {
-                    y += 20;
-                    writeln!(file, "<text x=\"{}\" y=\"{}\">{}</text>", x + 5, y, line)?;
-                }
-                writeln!(file, "</g>")?;
+    }
+    Ok(())
+}
+
+/// Return a round step between time axis graduations, for about ten of them.
+fn graduations_step(duration: TimeStamp) -> TimeStamp {
+    let mut step = 1;
+    loop {
+        for &factor in &[1, 2, 5] {
+            if duration / (step * factor) <= 10 {
+                return step * factor;
             }
         }
-
-        writeln!(file, "</g>")?;
+        step *= 10;
     }
+}
 
-    // this part will allow to get more info on tasks by hovering over them
+/// Draw a time axis starting at given (svg) position.
+fn write_time_axis(
+    file: &mut File,
+    origin: Point,
+    duration: TimeStamp,
+    xscale: f64,
+) -> Result<(), Error> {
+    let (x, y) = origin;
     writeln!(
         file,
-        "
-   <g id=\"tag_label_{id}\" transform=\"translate({tag_x}, {tag_y})\"></g>
-   <style>
-      .task-highlight {{
-        fill: #ec008c;
-        opacity: 1;
-      }}
-    </style>
-  <script><![CDATA[
-
-    var tags_{id} = [{tags}];
-    var current_tag_{id} = 0;
-    displayTips();
-    displayTags_{id}();
-
-    window.addEventListener('keydown', (event) => {{
-      
-        if (event.key === 'ArrowDown') {{
-            current_tag_{id} += 1;
-            if (current_tag_{id} == tags_{id}.length) {{
-                current_tag_{id} = 0;
-            }}
-            displayTags_{id}();
-        }}
-        if (event.key === 'ArrowUp') {{
-            if (current_tag_{id} == 0) {{
-                current_tag_{id} = tags_{id}.length -1;
-            }} else {{
-                current_tag_{id} -= 1;
-            }}
-            displayTags_{id}();
-        }}
-    }});
-
-    function displayTips() {{
-        let tasks = document.getElementsByClassName('task{id}');
-        for (let i = 0; i < tasks.length ; i++) {{
-          let task = tasks[i];
-          let tip_id = task.id;
-          let tip = document.getElementById('tip_{id}_'+tip_id);
-          tip.style.display='none';
-          tasks[i].tip = tip;
-          tasks[i].addEventListener('mouseover', mouseOverEffect);
-          tasks[i].addEventListener('mouseout', mouseOutEffect);
-        }}
-    }}
-
-    function displayTags_{id}() {{
-        tags_{id}.forEach(function(tag) {{
-            document.getElementById('tasks_colors_{id}_'+tag).style.display = 'none';
-        }});
-        document.getElementById('tasks_colors_{id}_'+tags_{id}[current_tag_{id}]).style.display = 'block';
-        document.getElementById('tag_label_{id}').innerHTML = \"<text>\"+tags_{id}[current_tag_{id}]+\"</text>\";
-    }}
-
-    function mouseOverEffect() {{
-      this.classList.add(\"task-highlight\");
-      this.tip.style.display='block';
-    }}
-
-    function mouseOutEffect() {{
-      this.classList.remove(\"task-highlight\");
-      this.tip.style.display='none';
-    }}
-  ]]></script>",
-        tag_x = svg_width - 300,
-        tag_y = 20,
-        tags = scene.tags.iter().map(|s| format!("\"{}\"", s)).join(", "),
-        id = random_id
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"2.0\"/>",
+        x,
+        y,
+        x + duration as f64 * xscale,
+        y
     )?;
+    let step = graduations_step(duration);
+    for time in (0..=duration).step_by(step as usize) {
+        let tick_x = x + time as f64 * xscale;
+        writeln!(
+            file,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"2.0\"/>",
+            tick_x,
+            y,
+            tick_x,
+            y + 10.0
+        )?;
+        writeln!(
+            file,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            tick_x,
+            y + 30.0,
+            time_string(time)
+        )?;
+    }
+    Ok(())
+}
 
-    write!(file, "</svg>")?;
+/// Display the color of each thread at given height.
+fn write_legend(scene: &Scene, file: &mut File, y: u32) -> Result<(), Error> {
+    for thread in 0..scene.threads_number {
+        let color = COLORS[thread % COLORS.len()];
+        let x = 20 + thread * 140;
+        writeln!(
+            file,
+            "<rect x=\"{}\" y=\"{}\" width=\"20\" height=\"20\" fill=\"rgb({},{},{})\" stroke=\"black\"/>",
+            x,
+            y,
+            (color[0] * 255.0) as u32,
+            (color[1] * 255.0) as u32,
+            (color[2] * 255.0) as u32,
+        )?;
+        writeln!(
+            file,
+            "<text x=\"{}\" y=\"{}\">thread {}</text>",
+            x + 25,
+            y + 16,
+            thread
+        )?;
+    }
     Ok(())
 }
 
@@ -397,3 +528,283 @@ pub(crate) fn histogram(
     write!(file, "</svg>")?;
     Ok(())
 }
+
+/// Draw a line chart with one curve for each algorithm, taking values at given
+/// numbers of threads.
+/// The reference curve (ideal values) is dashed.
+pub(crate) fn line_chart(
+    file: &mut File,
+    threads_numbers: &[usize],
+    curves: &[Vec<f64>],
+    reference: &[f64],
+) -> Result<(), Error> {
+    let (width, height, margin) = (800.0, 500.0, 60.0);
+    let min_threads = *threads_numbers.first().unwrap() as f64;
+    let max_threads = *threads_numbers.last().unwrap() as f64;
+    let max_value = curves
+        .iter()
+        .flatten()
+        .chain(reference)
+        .cloned()
+        .fold(0.0, f64::max)
+        * 1.1;
+    let x = |threads: usize| {
+        if max_threads > min_threads {
+            margin
+                + (threads as f64 - min_threads) / (max_threads - min_threads)
+                    * (width - 2.0 * margin)
+        } else {
+            width / 2.0
+        }
+    };
+    let y = |value: f64| {
+        height - margin - value / max_value.max(f64::EPSILON) * (height - 2.0 * margin)
+    };
+    write!(
+        file,
+        "<svg viewBox=\"0 0 {} {}\" width=\"{}\">",
+        width, height, width
+    )?;
+    write!(
+        file,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        width, height
+    )?;
+    // axes, with graduations for each number of threads and five values
+    write!(
+        file,
+        "<polyline points=\"{},{} {},{} {},{}\" fill=\"none\" stroke=\"black\"/>",
+        margin,
+        margin,
+        margin,
+        height - margin,
+        width - margin,
+        height - margin
+    )?;
+    for &threads in threads_numbers {
+        write!(
+            file,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x(threads),
+            height - margin + 20.0,
+            threads
+        )?;
+    }
+    write!(
+        file,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">threads</text>",
+        width / 2.0,
+        height - 10.0
+    )?;
+    for graduation in 1..=5 {
+        let value = max_value * graduation as f64 / 5.0;
+        write!(
+            file,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.2}</text>",
+            margin - 5.0,
+            y(value) + 5.0,
+            value
+        )?;
+    }
+    let points = |values: &[f64]| {
+        threads_numbers
+            .iter()
+            .zip(values)
+            .map(|(&threads, &value)| format!("{},{}", x(threads), y(value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    write!(
+        file,
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"black\" stroke-dasharray=\"5,5\"/>",
+        points(reference)
+    )?;
+    for (values, color) in curves.iter().zip(HISTOGRAM_COLORS.iter().cycle()) {
+        write!(
+            file,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+            points(values),
+            color
+        )?;
+        for (&threads, &value) in threads_numbers.iter().zip(values) {
+            write!(
+                file,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"{}\"><title>{:.2}</title></circle>",
+                x(threads),
+                y(value),
+                color,
+                value
+            )?;
+        }
+    }
+    write!(file, "</svg>")?;
+    Ok(())
+}
+
+/// Draw durations against input sizes on logarithmic scales, one curve for each algorithm.
+/// Given sizes (where an algorithm overtakes another one) are highlighted.
+pub(crate) fn log_log_chart(
+    file: &mut File,
+    curves: &[Vec<(usize, TimeStamp)>],
+    highlighted_sizes: &[f64],
+) -> Result<(), Error> {
+    let (width, height, margin) = (800.0, 500.0, 70.0);
+    let bounds = |values: &mut dyn Iterator<Item = f64>| {
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        // leave some room around single values
+        if max - min < 1.0 {
+            ((min - 0.5).floor(), (max + 0.5).ceil())
+        } else {
+            (min.floor(), max.ceil())
+        }
+    };
+    let (xmin, xmax) = bounds(
+        &mut curves
+            .iter()
+            .flatten()
+            .map(|&(size, _)| (size.max(1) as f64).log10()),
+    );
+    let (ymin, ymax) = bounds(
+        &mut curves
+            .iter()
+            .flatten()
+            .map(|&(_, duration)| (duration.max(1) as f64).log10()),
+    );
+    let x = |size: f64| {
+        margin + (size.max(1.0).log10() - xmin) / (xmax - xmin) * (width - 2.0 * margin)
+    };
+    let y = |duration: f64| {
+        height
+            - margin
+            - (duration.max(1.0).log10() - ymin) / (ymax - ymin) * (height - 2.0 * margin)
+    };
+    write!(
+        file,
+        "<svg viewBox=\"0 0 {} {}\" width=\"{}\">",
+        width, height, width
+    )?;
+    write!(
+        file,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        width, height
+    )?;
+    write!(
+        file,
+        "<polyline points=\"{},{} {},{} {},{}\" fill=\"none\" stroke=\"black\"/>",
+        margin,
+        margin,
+        margin,
+        height - margin,
+        width - margin,
+        height - margin
+    )?;
+    // graduations on powers of ten
+    for power in xmin as i32..=xmax as i32 {
+        let size = 10f64.powi(power);
+        write!(
+            file,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x(size),
+            height - margin + 20.0,
+            size
+        )?;
+    }
+    write!(
+        file,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">input size</text>",
+        width / 2.0,
+        height - 10.0
+    )?;
+    for power in ymin as i32..=ymax as i32 {
+        let duration = 10f64.powi(power);
+        write!(
+            file,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            margin - 5.0,
+            y(duration) + 5.0,
+            time_string(duration as TimeStamp)
+        )?;
+    }
+    for &size in highlighted_sizes {
+        write!(
+            file,
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"grey\" stroke-dasharray=\"5,5\"/>",
+            x(size),
+            margin,
+            height - margin
+        )?;
+    }
+    for (points, color) in curves.iter().zip(HISTOGRAM_COLORS.iter().cycle()) {
+        write!(
+            file,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+            points
+                .iter()
+                .map(|&(size, duration)| format!("{},{}", x(size as f64), y(duration as f64)))
+                .join(" "),
+            color
+        )?;
+        for &(size, duration) in points {
+            write!(
+                file,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"{}\"><title>{}: {}</title></circle>",
+                x(size as f64),
+                y(duration as f64),
+                color,
+                size,
+                time_string(duration)
+            )?;
+        }
+    }
+    write!(file, "</svg>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+
+    fn render(scene: &Scene, options: &SvgOptions, name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rayon_logs_{}.svg", name));
+        write_svg_file(scene, &path, options).unwrap();
+        let svg = read_to_string(&path).unwrap();
+        std::fs::remove_file(path).ok();
+        svg
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        let information: HashMap<String, (String, f64)> = vec![(
+            "_NO_TAGS_".to_string(),
+            ("</title><script>\"x\" > y & z".to_string(), 1.0),
+        )]
+        .into_iter()
+        .collect();
+        let scene = Scene {
+            rectangles: vec![Rectangle::new(
+                COLORS[0],
+                (0.0, 0.0),
+                (10.0, 10.0),
+                (0, 10),
+                information,
+            )],
+            segments: Vec::new(),
+            arrows: Vec::new(),
+            tags: vec!["_NO_TAGS_".to_string()],
+            threads_number: 1,
+            time_axis: None,
+        };
+        let escaped = "<title>&lt;/title&gt;&lt;script&gt;&quot;x&quot; &gt; y &amp; z</title>";
+        for (options, name) in &[
+            (SvgOptions::static_svg(), "static_escapes"),
+            (SvgOptions::default(), "animated_escapes"),
+        ] {
+            let svg = render(&scene, options, name);
+            assert!(svg.contains(escaped));
+            assert!(!svg.contains("<script"));
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Structural checks on logs.
+//! Logs we generate are valid by construction but hand-edited or externally generated logs
+//! might not be, and the display code relies on all these invariants.
+use crate::log::RunLog;
+use crate::raw_events::TaskId;
+use itertools::Itertools;
+use std::collections::HashSet;
+use std::fmt;
+
+/// A broken invariant in a `RunLog` (see `RunLog::validate`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A task has a child which does not exist.
+    ChildOutOfRange {
+        /// the father
+        task: TaskId,
+        /// the non existing child
+        child: TaskId,
+    },
+    /// A task ends before it starts.
+    EndsBeforeStart {
+        /// the task
+        task: TaskId,
+    },
+    /// A task runs on a thread which does not exist.
+    ThreadOutOfRange {
+        /// the task
+        task: TaskId,
+        /// its thread
+        thread: usize,
+    },
+    /// Two tasks run at the same time on the same thread.
+    Overlap {
+        /// the thread
+        thread: usize,
+        /// first task (by starting time)
+        first: TaskId,
+        /// second task, starting before the first one ends
+        second: TaskId,
+    },
+    /// The graph contains a cycle going through given task.
+    Cycle {
+        /// a task on the cycle
+        task: TaskId,
+    },
+    /// There is not exactly one task without father.
+    RootsNumber {
+        /// all tasks without father
+        roots: Vec<TaskId>,
+    },
+    /// A subgraph refers to a non existing task or tag.
+    SubgraphOutOfRange {
+        /// index of the subgraph
+        subgraph: usize,
+    },
+    /// A subgraph's start task is not an ancestor of its end task.
+    SubgraphNotConnected {
+        /// index of the subgraph
+        subgraph: usize,
+        /// starting task
+        start: TaskId,
+        /// ending task
+        end: TaskId,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::ChildOutOfRange { task, child } => {
+                write!(f, "task {} has a non existing child {}", task, child)
+            }
+            Violation::EndsBeforeStart { task } => write!(f, "task {} ends before starting", task),
+            Violation::ThreadOutOfRange { task, thread } => {
+                write!(f, "task {} runs on non existing thread {}", task, thread)
+            }
+            Violation::Overlap {
+                thread,
+                first,
+                second,
+            } => write!(
+                f,
+                "tasks {} and {} overlap on thread {}",
+                first, second, thread
+            ),
+            Violation::Cycle { task } => write!(f, "task {} is on a cycle", task),
+            Violation::RootsNumber { roots } => write!(
+                f,
+                "graph should have exactly one root but has {} ({:?})",
+                roots.len(),
+                roots
+            ),
+            Violation::SubgraphOutOfRange { subgraph } => write!(
+                f,
+                "subgraph {} refers to a non existing task or tag",
+                subgraph
+            ),
+            Violation::SubgraphNotConnected {
+                subgraph,
+                start,
+                end,
+            } => write!(
+                f,
+                "subgraph {} starts on task {} which is not an ancestor of its end task {}",
+                subgraph, start, end
+            ),
+        }
+    }
+}
+
+/// Return all violations found in given log.
+pub(crate) fn violations(log: &RunLog) -> Vec<Violation> {
+    let tasks = &log.tasks_logs;
+    let mut violations = Vec::new();
+
+    for (task_id, task) in tasks.iter().enumerate() {
+        violations.extend(
+            task.children
+                .iter()
+                .filter(|&&child| child >= tasks.len())
+                .map(|&child| Violation::ChildOutOfRange {
+                    task: task_id,
+                    child,
+                }),
+        );
+        if task.end_time < task.start_time {
+            violations.push(Violation::EndsBeforeStart { task: task_id });
+        }
+        if task.thread_id >= log.threads_number {
+            violations.push(Violation::ThreadOutOfRange {
+                task: task_id,
+                thread: task.thread_id,
+            });
+        }
+    }
+
+    // tasks of each thread, by starting time
+    let threads_tasks = (0..tasks.len())
+        .map(|task| (tasks[task].thread_id, task))
+        .into_group_map();
+    for (thread, thread_tasks) in threads_tasks.into_iter().sorted_by_key(|(t, _)| *t) {
+        let mut running: Option<TaskId> = None; // task ending last so far
+        for task in thread_tasks
+            .into_iter()
+            .sorted_by_key(|t| (tasks[*t].start_time, *t))
+        {
+            if let Some(previous) = running {
+                if tasks[task].start_time < tasks[previous].end_time {
+                    violations.push(Violation::Overlap {
+                        thread,
+                        first: previous,
+                        second: task,
+                    });
+                }
+                if tasks[task].end_time > tasks[previous].end_time {
+                    running = Some(task);
+                }
+            } else {
+                running = Some(task);
+            }
+        }
+    }
+
+    violations.extend(
+        cycles(log)
+            .into_iter()
+            .map(|task| Violation::Cycle { task }),
+    );
+
+    // flight recorder logs lose links to overwritten tasks and therefore have several roots
+    if !log.partial {
+        let children: HashSet<TaskId> = tasks
+            .iter()
+            .flat_map(|t| t.children.iter())
+            .cloned()
+            .collect();
+        let roots: Vec<TaskId> = (0..tasks.len()).filter(|t| !children.contains(t)).collect();
+        if roots.len() != 1 {
+            violations.push(Violation::RootsNumber { roots });
+        }
+    }
+
+    for (subgraph, &(start, end, tag, _)) in log.subgraphs.iter().enumerate() {
+        if start >= tasks.len() || end >= tasks.len() || tag >= log.tags.len() {
+            violations.push(Violation::SubgraphOutOfRange { subgraph });
+        } else if !is_ancestor(log, start, end) {
+            violations.push(Violation::SubgraphNotConnected {
+                subgraph,
+                start,
+                end,
+            });
+        }
+    }
+    violations
+}
+
+/// Return one task on each cycle we encounter (depth first search).
+fn cycles(log: &RunLog) -> Vec<TaskId> {
+    let tasks = &log.tasks_logs;
+    let valid_children = |task: TaskId| {
+        tasks[task]
+            .children
+            .iter()
+            .cloned()
+            .filter(|&c| c < tasks.len())
+    };
+    // 0: not seen, 1: on the stack, 2: done
+    let mut states = vec![0u8; tasks.len()];
+    let mut cycles = Vec::new();
+    for root in 0..tasks.len() {
+        if states[root] != 0 {
+            continue;
+        }
+        states[root] = 1;
+        let mut stack = vec![(root, valid_children(root))];
+        while let Some((task, children)) = stack.last_mut() {
+            let task = *task;
+            match children.next() {
+                Some(child) => match states[child] {
+                    0 => {
+                        states[child] = 1;
+                        stack.push((child, valid_children(child)));
+                    }
+                    1 => cycles.push(child),
+                    _ => (),
+                },
+                None => {
+                    states[task] = 2;
+                    stack.pop();
+                }
+            }
+        }
+    }
+    cycles.sort();
+    cycles.dedup();
+    cycles
+}
+
+/// Is there a path from ancestor to task.
+/// Children start after their fathers so we do not explore tasks starting after our target.
+fn is_ancestor(log: &RunLog, ancestor: TaskId, task: TaskId) -> bool {
+    let tasks = &log.tasks_logs;
+    let limit = tasks[task].start_time;
+    let mut seen = HashSet::new();
+    let mut stack = vec![ancestor];
+    while let Some(current) = stack.pop() {
+        if current == task {
+            return true;
+        }
+        if seen.insert(current) {
+            stack.extend(
+                tasks[current]
+                    .children
+                    .iter()
+                    .cloned()
+                    .filter(|&c| c < tasks.len() && tasks[c].start_time <= limit),
+            );
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a tagged log from (start, end, thread, children) tuples.
+    fn log_from(tasks: &[(u64, u64, usize, &[TaskId])]) -> RunLog {
+        let mut log = RunLog::from_tasks(2, tasks);
+        log.tags = vec!["tag".to_string()];
+        log
+    }
+
+    #[test]
+    fn fork_join_is_valid() {
+        let mut log = log_from(&[
+            (0, 10, 0, &[1, 2]),
+            (10, 100, 0, &[3]),
+            (20, 200, 1, &[3]),
+            (200, 210, 0, &[]),
+        ]);
+        log.subgraphs.push((0, 3, 0, 1));
+        assert_eq!(violations(&log), Vec::new());
+    }
+
+    #[test]
+    fn cycles_are_detected() {
+        let log = log_from(&[
+            (0, 10, 0, &[1]),
+            (10, 20, 0, &[2]),
+            (20, 30, 0, &[1]),
+            (30, 40, 1, &[3]),
+        ]);
+        assert_eq!(cycles(&log), vec![1, 3]);
+        assert_eq!(
+            violations(&log),
+            vec![Violation::Cycle { task: 1 }, Violation::Cycle { task: 3 }]
+        );
+    }
+
+    #[test]
+    fn overlaps_are_detected() {
+        // task 0 contains task 1 and task 2 starts before task 0 ends
+        let log = log_from(&[
+            (0, 100, 0, &[1, 2]),
+            (10, 20, 0, &[]),
+            (30, 110, 0, &[]),
+            (0, 50, 1, &[]),
+        ]);
+        assert_eq!(
+            violations(&log),
+            vec![
+                Violation::Overlap {
+                    thread: 0,
+                    first: 0,
+                    second: 1
+                },
+                Violation::Overlap {
+                    thread: 0,
+                    first: 0,
+                    second: 2
+                },
+                Violation::RootsNumber { roots: vec![0, 3] },
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_references_are_detected() {
+        let mut log = log_from(&[(10, 0, 2, &[1, 5]), (20, 30, 0, &[])]);
+        log.subgraphs.push((1, 0, 0, 1));
+        log.subgraphs.push((0, 1, 3, 1));
+        assert_eq!(
+            violations(&log),
+            vec![
+                Violation::ChildOutOfRange { task: 0, child: 5 },
+                Violation::EndsBeforeStart { task: 0 },
+                Violation::ThreadOutOfRange { task: 0, thread: 2 },
+                Violation::SubgraphNotConnected {
+                    subgraph: 0,
+                    start: 1,
+                    end: 0
+                },
+                Violation::SubgraphOutOfRange { subgraph: 1 },
+            ]
+        );
+    }
+}